@@ -1,48 +1,498 @@
+use crate::kanji_grade::{kanji_level, KanjiLevel};
+use crate::romaji::{kana_to_romaji, RomajiStyle};
 use lindera::dictionary::DictionaryKind;
-use lindera::mode::Mode;
+use lindera::mode::{Mode, Penalty};
 use lindera::tokenizer::Tokenizer;
 use serde::{Deserialize, Serialize};
-use std::sync::Once;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use unicode_segmentation::UnicodeSegmentation;
 
-static INIT: Once = Once::new();
-static mut TOKENIZER: Option<Tokenizer> = None;
+// 読み込んだトークナイザと、その辞書の種類・ユーザー辞書
+struct LoadedTokenizer {
+    kind: DictionaryKind,
+    user_dictionary: Option<PathBuf>,
+    tokenizer: TokenizerHandle,
+}
+
+// 組み込みの辞書だけのトークナイザは OnceLock に置いたものを共有し、
+// ユーザー辞書や分解モードのトークナイザは読み込んだものを持つ
+enum TokenizerHandle {
+    Builtin(&'static Tokenizer),
+    Owned(Box<Tokenizer>),
+}
 
-pub fn create_tokenizer() -> Result<(), Box<dyn std::error::Error>> {
-    unsafe {
-        if TOKENIZER.is_none() {
-            let dictionary =
-                lindera::dictionary::load_dictionary_from_kind(DictionaryKind::IPADIC)?;
-            let segmenter = lindera::segmenter::Segmenter::new(Mode::Normal, dictionary, None);
-            TOKENIZER = Some(Tokenizer::new(segmenter));
+impl LoadedTokenizer {
+    fn tokenizer(&self) -> &Tokenizer {
+        match &self.tokenizer {
+            TokenizerHandle::Builtin(tokenizer) => tokenizer,
+            TokenizerHandle::Owned(tokenizer) => tokenizer,
         }
-        Ok(())
     }
 }
 
-fn get_tokenizer() -> &'static Tokenizer {
-    unsafe {
-        if TOKENIZER.is_none() {
-            create_tokenizer().expect("Failed to initialize tokenizer");
+type BuiltinTokenizer = OnceLock<Result<Tokenizer, String>>;
+
+// 辞書の種類ごとに一度だけ初期化する（同時に呼ばれても初期化は一つのスレッドだけが行う）
+fn builtin_tokenizer_slot(kind: &DictionaryKind) -> &'static BuiltinTokenizer {
+    static IPADIC: BuiltinTokenizer = OnceLock::new();
+    static IPADIC_NEOLOGD: BuiltinTokenizer = OnceLock::new();
+    static UNIDIC: BuiltinTokenizer = OnceLock::new();
+    static KO_DIC: BuiltinTokenizer = OnceLock::new();
+    static CC_CEDICT: BuiltinTokenizer = OnceLock::new();
+    match kind {
+        DictionaryKind::IPADIC => &IPADIC,
+        DictionaryKind::IPADICNEologd => &IPADIC_NEOLOGD,
+        DictionaryKind::UniDic => &UNIDIC,
+        DictionaryKind::KoDic => &KO_DIC,
+        DictionaryKind::CcCedict => &CC_CEDICT,
+    }
+}
+
+// 組み込みの辞書だけのトークナイザ（プロセスの終了まで保持するので &'static で返せる）
+fn builtin_tokenizer(
+    kind: &DictionaryKind,
+) -> Result<&'static Tokenizer, Box<dyn std::error::Error>> {
+    builtin_tokenizer_slot(kind)
+        .get_or_init(|| new_tokenizer(kind, None, Mode::Normal).map_err(|e| e.to_string()))
+        .as_ref()
+        .map_err(|e| e.clone().into())
+}
+
+// 使用する辞書（既定は IPADIC）。破棄した後に読み込み直すときもこの辞書を使う
+static DICTIONARY_KIND: RwLock<DictionaryKind> = RwLock::new(DictionaryKind::IPADIC);
+
+// 使用するユーザー辞書の CSV（専門用語などを一語として扱うため）
+static USER_DICTIONARY: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+// 解析中の呼び出しはそれぞれ Arc を保持するので、unload_tokenizer で外しても
+// 辞書のメモリはそれらの解析が終わった時点で解放される
+static TOKENIZER: RwLock<Option<Arc<LoadedTokenizer>>> = RwLock::new(None);
+
+// 曖昧さの検出に使う分解モードのトークナイザ（初めて使うときに読み込み、TOKENIZER と一緒に手放す）
+static DECOMPOSE_TOKENIZER: RwLock<Option<Arc<LoadedTokenizer>>> = RwLock::new(None);
+
+// 境界判定などのデバッグ出力を標準エラーに書くかどうか（既定は出力しない）
+static DEBUG_LOGGING: AtomicBool = AtomicBool::new(false);
+
+pub fn set_debug_logging(enabled: bool) {
+    DEBUG_LOGGING.store(enabled, Ordering::Relaxed);
+}
+
+pub fn debug_logging_enabled() -> bool {
+    DEBUG_LOGGING.load(Ordering::Relaxed)
+}
+
+// デバッグ出力が有効なときだけ書式化して出力する（無効なら引数の書式化もしない）
+macro_rules! debug_log {
+    ($($arg:tt)*) => {
+        if debug_logging_enabled() {
+            write_debug_log(format_args!($($arg)*));
+        }
+    };
+}
+
+#[cfg(not(test))]
+fn write_debug_log(args: std::fmt::Arguments) {
+    eprintln!("{}", args);
+}
+
+// テストでは標準エラーの代わりにスレッドごとのバッファに書き、出力の有無を確かめられるようにする
+#[cfg(test)]
+thread_local! {
+    static DEBUG_OUTPUT: std::cell::RefCell<Vec<String>> = const { std::cell::RefCell::new(Vec::new()) };
+}
+
+#[cfg(test)]
+fn write_debug_log(args: std::fmt::Arguments) {
+    DEBUG_OUTPUT.with(|output| output.borrow_mut().push(args.to_string()));
+}
+
+pub fn default_dictionary_kind() -> DictionaryKind {
+    DICTIONARY_KIND
+        .read()
+        .map(|kind| kind.clone())
+        .unwrap_or(DictionaryKind::IPADIC)
+}
+
+fn user_dictionary_path() -> Option<PathBuf> {
+    USER_DICTIONARY.read().ok().and_then(|path| path.clone())
+}
+
+// 指定した辞書でトークナイザを初期化し、以後の既定の辞書にする
+// 同じ辞書で読み込み済みなら読み込み直さない
+// 返すのは組み込みの辞書だけのトークナイザで、ユーザー辞書を読み込んでいても含まない
+pub fn create_tokenizer(
+    kind: DictionaryKind,
+) -> Result<&'static Tokenizer, Box<dyn std::error::Error>> {
+    replace_tokenizer(kind.clone(), user_dictionary_path(), true)?;
+    builtin_tokenizer(&kind)
+}
+
+// ユーザー辞書の CSV を読み込んでトークナイザを初期化し直す
+// 同じファイルでも内容が変わっている場合があるので、常に読み込み直す
+pub fn create_tokenizer_with_user_dict(csv_path: &Path) -> Result<(), BunsetsuError> {
+    validate_user_dictionary(csv_path)
+        .and_then(|_| {
+            replace_tokenizer(
+                default_dictionary_kind(),
+                Some(csv_path.to_path_buf()),
+                false,
+            )
+        })
+        .map_err(|e| BunsetsuError::DictionaryLoad(e.to_string()))
+}
+
+fn replace_tokenizer(
+    kind: DictionaryKind,
+    user_dictionary: Option<PathBuf>,
+    keep_if_loaded: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut slot = TOKENIZER.write().map_err(|e| e.to_string())?;
+    if keep_if_loaded
+        && slot
+            .as_ref()
+            .is_some_and(|loaded| loaded.kind == kind && loaded.user_dictionary == user_dictionary)
+    {
+        return Ok(());
+    }
+
+    *slot = Some(Arc::new(build_tokenizer(
+        kind.clone(),
+        user_dictionary.clone(),
+    )?));
+    *DICTIONARY_KIND.write().map_err(|e| e.to_string())? = kind;
+    *USER_DICTIONARY.write().map_err(|e| e.to_string())? = user_dictionary;
+    drop(slot);
+
+    // 以前の辞書で解析したトークン列や、以前の辞書の分解モードのトークナイザは使えない
+    DECOMPOSE_TOKENIZER
+        .write()
+        .map_err(|e| e.to_string())?
+        .take();
+    clear_cache();
+    Ok(())
+}
+
+// ユーザー辞書がなければ組み込みの辞書のトークナイザを共有する
+fn build_tokenizer(
+    kind: DictionaryKind,
+    user_dictionary: Option<PathBuf>,
+) -> Result<LoadedTokenizer, Box<dyn std::error::Error>> {
+    let tokenizer = match &user_dictionary {
+        Some(path) => {
+            TokenizerHandle::Owned(Box::new(new_tokenizer(&kind, Some(path), Mode::Normal)?))
+        }
+        None => TokenizerHandle::Builtin(builtin_tokenizer(&kind)?),
+    };
+    Ok(LoadedTokenizer {
+        kind,
+        user_dictionary,
+        tokenizer,
+    })
+}
+
+fn build_tokenizer_with_mode(
+    kind: DictionaryKind,
+    user_dictionary: Option<PathBuf>,
+    mode: Mode,
+) -> Result<LoadedTokenizer, Box<dyn std::error::Error>> {
+    let tokenizer = new_tokenizer(&kind, user_dictionary.as_deref(), mode)?;
+    Ok(LoadedTokenizer {
+        kind,
+        user_dictionary,
+        tokenizer: TokenizerHandle::Owned(Box::new(tokenizer)),
+    })
+}
+
+fn new_tokenizer(
+    kind: &DictionaryKind,
+    user_dictionary: Option<&Path>,
+    mode: Mode,
+) -> Result<Tokenizer, Box<dyn std::error::Error>> {
+    let dictionary = lindera::dictionary::load_dictionary_from_kind(kind.clone())?;
+    let user_dict = match user_dictionary {
+        Some(path) => Some(lindera::dictionary::load_user_dictionary_from_csv(
+            kind.clone(),
+            path,
+        )?),
+        None => None,
+    };
+    let segmenter = lindera::segmenter::Segmenter::new(mode, dictionary, user_dict);
+    Ok(Tokenizer::new(segmenter))
+}
+
+// ユーザー辞書の CSV の各行を検査する
+// 1 行は「表層形,品詞,読み」の簡易形式か、辞書の形式どおりの詳細形式（13 列以上）で書く
+fn validate_user_dictionary(csv_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let content = std::fs::read_to_string(csv_path).map_err(|e| {
+        format!(
+            "ユーザー辞書 {} を読み込めません: {}",
+            csv_path.display(),
+            e
+        )
+    })?;
+
+    for (index, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(',').collect();
+        if fields.len() != 3 && fields.len() < 13 {
+            return Err(format!(
+                "ユーザー辞書 {} の {} 行目は {} 列です（「表層形,品詞,読み」の 3 列か、13 列以上の詳細形式で書いてください）",
+                csv_path.display(),
+                index + 1,
+                fields.len()
+            )
+            .into());
+        }
+        if fields[0].trim().is_empty() {
+            return Err(format!(
+                "ユーザー辞書 {} の {} 行目の表層形が空です",
+                csv_path.display(),
+                index + 1
+            )
+            .into());
         }
-        TOKENIZER.as_ref().unwrap()
     }
+
+    Ok(())
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+// 読み込み済みならそれを返し、未読み込みなら既定の辞書を読み込む
+fn load_tokenizer() -> Result<Arc<LoadedTokenizer>, Box<dyn std::error::Error>> {
+    let mut slot = TOKENIZER.write().map_err(|e| e.to_string())?;
+    if let Some(loaded) = slot.as_ref() {
+        return Ok(Arc::clone(loaded));
+    }
+
+    let loaded = Arc::new(build_tokenizer(
+        default_dictionary_kind(),
+        user_dictionary_path(),
+    )?);
+    *slot = Some(Arc::clone(&loaded));
+    Ok(loaded)
+}
+
+fn get_tokenizer() -> Result<Arc<LoadedTokenizer>, Box<dyn std::error::Error>> {
+    if let Some(loaded) = TOKENIZER.read().map_err(|e| e.to_string())?.as_ref() {
+        return Ok(Arc::clone(loaded));
+    }
+    load_tokenizer()
+}
+
+// 通常のトークナイザと同じ辞書の分解モードのトークナイザを返す（辞書が変わっていれば読み込み直す）
+fn get_decompose_tokenizer(
+    loaded: &LoadedTokenizer,
+) -> Result<Arc<LoadedTokenizer>, Box<dyn std::error::Error>> {
+    let same_dictionary = |decompose: &LoadedTokenizer| {
+        decompose.kind == loaded.kind && decompose.user_dictionary == loaded.user_dictionary
+    };
+    if let Some(decompose) = DECOMPOSE_TOKENIZER
+        .read()
+        .map_err(|e| e.to_string())?
+        .as_ref()
+        .filter(|decompose| same_dictionary(decompose))
+    {
+        return Ok(Arc::clone(decompose));
+    }
+
+    let mut slot = DECOMPOSE_TOKENIZER.write().map_err(|e| e.to_string())?;
+    if let Some(decompose) = slot.as_ref().filter(|decompose| same_dictionary(decompose)) {
+        return Ok(Arc::clone(decompose));
+    }
+    let decompose = Arc::new(build_tokenizer_with_mode(
+        loaded.kind.clone(),
+        loaded.user_dictionary.clone(),
+        Mode::Decompose(Penalty::default()),
+    )?);
+    *slot = Some(Arc::clone(&decompose));
+    Ok(decompose)
+}
+
+// トークナイザを破棄して辞書のメモリを解放する（メモリの少ない端末で使っていない間など）
+// 次に解析するときに自動で読み込み直す
+// 組み込みの辞書は create_tokenizer が &'static で返すので残り、ユーザー辞書と分解モードの分だけ解放される
+pub fn unload_tokenizer() -> Result<(), BunsetsuError> {
+    TOKENIZER
+        .write()
+        .map_err(|e| BunsetsuError::Internal(e.to_string()))?
+        .take();
+    DECOMPOSE_TOKENIZER
+        .write()
+        .map_err(|e| BunsetsuError::Internal(e.to_string()))?
+        .take();
+    // キャッシュしている解析結果も手放す
+    clear_cache();
+    Ok(())
+}
+
+// 辞書の選択に関するエラー
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", content = "detail")]
+pub enum DictionaryError {
+    // 辞書名として解釈できない
+    #[serde(rename = "UnknownDictionary")]
+    Unknown(String),
+    // ビルド時に有効化されていない辞書が指定された
+    #[serde(rename = "DictionaryUnavailable")]
+    Unavailable { dictionary: String, feature: String },
+    // 辞書の読み込みに失敗した
+    #[serde(rename = "DictionaryLoad")]
+    Load(String),
+}
+
+impl std::fmt::Display for DictionaryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DictionaryError::Unknown(name) => write!(f, "不明な辞書です: {}", name),
+            DictionaryError::Unavailable {
+                dictionary,
+                feature,
+            } => write!(
+                f,
+                "辞書 {} はこのビルドに含まれていません（feature \"{}\" を有効にしてビルドしてください）",
+                dictionary, feature
+            ),
+            DictionaryError::Load(message) => write!(f, "辞書の読み込みに失敗しました: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for DictionaryError {}
+
+// このビルドで使用できる辞書の一覧
+pub fn available_dictionary_kinds() -> Vec<String> {
+    DictionaryKind::contained_variants()
+        .iter()
+        .map(|kind| kind.as_str().to_string())
+        .collect()
+}
+
+// 使用する辞書を指定する
+// ビルドに含まれていない辞書はトークナイザを触る前に DictionaryUnavailable で弾く
+pub fn select_dictionary(name: &str) -> Result<(), DictionaryError> {
+    let kind: DictionaryKind = name
+        .parse()
+        .map_err(|_| DictionaryError::Unknown(name.to_string()))?;
+
+    if !DictionaryKind::contained_variants().contains(&kind) {
+        return Err(DictionaryError::Unavailable {
+            dictionary: kind.as_str().to_string(),
+            feature: format!("lindera/{}", kind.as_str()),
+        });
+    }
+
+    create_tokenizer(kind)
+        .map(|_| ())
+        .map_err(|e| DictionaryError::Load(e.to_string()))
+}
+
+// 文節分割・形態素解析のエラー（フロントエンドで kind により処理を分けられるようにする）
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", content = "detail")]
+pub enum BunsetsuError {
+    // トークナイザを初期化できない
+    TokenizerInit(String),
+    // 形態素解析に失敗した
+    Tokenize(String),
+    // 入力が空（空白だけの場合も含む）
+    EmptyInput,
+    // ユーザー辞書を読み込めない
+    DictionaryLoad(String),
+    // 引数の値が不正（0 以下の読む速さ、入力と一致しない正解の文節など）
+    InvalidArgument(String),
+    // prepare_document で解析していない文書 ID
+    UnknownDocument(String),
+    // ロックの破損や結果の送信の失敗など、入力によらない内部のエラー
+    Internal(String),
+}
+
+impl std::fmt::Display for BunsetsuError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BunsetsuError::TokenizerInit(message) => {
+                write!(f, "トークナイザの初期化に失敗しました: {}", message)
+            }
+            BunsetsuError::Tokenize(message) => write!(f, "形態素解析に失敗しました: {}", message),
+            BunsetsuError::EmptyInput => write!(f, "テキストが入力されていません"),
+            BunsetsuError::DictionaryLoad(message) => {
+                write!(f, "ユーザー辞書の読み込みに失敗しました: {}", message)
+            }
+            BunsetsuError::InvalidArgument(message) => write!(f, "{}", message),
+            BunsetsuError::UnknownDocument(doc_id) => {
+                write!(f, "解析されていない文書です: {}", doc_id)
+            }
+            BunsetsuError::Internal(message) => write!(f, "内部エラー: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for BunsetsuError {}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct WordInfo {
     text: String,
     pos: String,
+    // 振り仮名用の読み（ひらがな）。記号や未知語など読みのないトークンは None
+    reading: Option<String>,
+    // 辞書形（食べた → 食べる）。活用しない語（名詞・助詞など）は表記と同じ、記号は None
+    base_form: Option<String>,
+    // 語彙リスト用の分類（カタカナの外来語は "loanword"）。該当しなければ None
+    category: Option<String>,
+}
+
+// 辞書ごとの素性の並び。品詞と品詞細分類（0〜2 列目）はどの辞書でも同じ位置
+#[derive(Debug, Clone, Copy)]
+struct FeatureLayout {
+    conjugation_type: usize,
+    conjugation_form: usize,
+    base_form: usize,
+    reading: usize,
+}
+
+impl FeatureLayout {
+    fn for_kind(kind: &DictionaryKind) -> FeatureLayout {
+        match kind {
+            // 品詞,細分類1,細分類2,細分類3,活用型,活用形,語彙素読み,語彙素,書字形,発音形,...
+            // 表記どおりの読みに最も近い発音形を読みとして使う
+            DictionaryKind::UniDic => FeatureLayout {
+                conjugation_type: 4,
+                conjugation_form: 5,
+                base_form: 7,
+                reading: 9,
+            },
+            // 品詞,細分類1,細分類2,細分類3,活用型,活用形,原形,読み,発音
+            _ => FeatureLayout {
+                conjugation_type: 4,
+                conjugation_form: 5,
+                base_form: 6,
+                reading: 7,
+            },
+        }
+    }
 }
 
 // トークン情報を保持する構造体
+#[derive(Clone)]
 struct TokenInfo {
     text: String,
     features: Vec<String>,
+    layout: FeatureLayout,
+    // 解析したテキストでの開始位置（バイト単位）
+    byte_start: usize,
 }
 
 impl TokenInfo {
     fn pos(&self) -> &str {
-        self.features.get(0).map(|s| s.as_str()).unwrap_or("未知語")
+        self.features
+            .first()
+            .map(|s| s.as_str())
+            .unwrap_or("未知語")
     }
 
     fn pos_detail_1(&self) -> Option<&str> {
@@ -54,28 +504,156 @@ impl TokenInfo {
     }
 
     fn conjugation_form(&self) -> Option<&str> {
-        self.features.get(5).map(|s| s.as_str())
+        self.features
+            .get(self.layout.conjugation_form)
+            .map(|s| s.as_str())
     }
 
     fn conjugation_type(&self) -> Option<&str> {
-        self.features.get(4).map(|s| s.as_str())
+        self.features
+            .get(self.layout.conjugation_type)
+            .map(|s| s.as_str())
     }
 
     fn base_form(&self) -> Option<&str> {
-        self.features.get(6).map(|s| s.as_str())
+        self.features.get(self.layout.base_form).map(|s| s.as_str())
     }
 
     fn reading(&self) -> Option<&str> {
-        self.features.get(7).map(|s| s.as_str())
+        self.features.get(self.layout.reading).map(|s| s.as_str())
+    }
+}
+
+// 文節境界の判定オプション
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BoundaryOptions {
+    // 連続する助詞（には・では・とは）を別々の文節にする
+    pub split_compound_particles: bool,
+    // 解析前に口語の縮約形を展開する（てる→ている、なきゃ→なければ など）
+    pub expand_contractions: bool,
+    // こそあどの連体詞（この・その・あの・どの）を独立した文節にする
+    pub split_demonstratives: bool,
+    // 述語に続く助動詞などがこの数に達したら、次の「た・ます・たい・ない」の前で一度だけ区切る
+    // （食べさせられ／たくなかった）。None なら区切らない
+    pub auxiliary_chain_limit: Option<usize>,
+    // 先に文に分けてから文ごとに文節に分ける（文末をまたいで文節がつながらない）
+    pub split_by_sentence: bool,
+    // 文節境界の判定方法
+    pub strategy: SplitStrategy,
+    // 解析前に文字を正規化する（None なら入力の文字をそのまま解析する）
+    pub normalize: Option<NormalizeOptions>,
+    // 「」『』で囲まれた引用の扱い
+    pub quote_mode: QuoteMode,
+}
+
+// 引用（「」『』）の文節の扱い
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum QuoteMode {
+    // 引用の中も通常どおり文節に分ける
+    #[default]
+    Split,
+    // 括弧を含めて SHORT_QUOTE_MAX_CHARS 文字以下の引用は 1 つの文節にする
+    KeepShort,
+}
+
+// QuoteMode::KeepShort で 1 つの文節にする引用の最大文字数（括弧を含む）
+const SHORT_QUOTE_MAX_CHARS: usize = 12;
+
+// 文節境界の判定方法
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum SplitStrategy {
+    // 品詞・活用形ごとの規則で判定する
+    #[default]
+    RuleBased,
+    // 内容語と助詞・助動詞の並びだけで判定する簡易な方法
+    // 内容語の後ろに助詞・助動詞が付いた文節に次の内容語が来たら区切り、長い文節は
+    // SIMPLE_MAX_PHRASE_CHARS 文字で区切る
+    Simple,
+}
+
+// 簡易な判定方法での文節の最大文字数
+const SIMPLE_MAX_PHRASE_CHARS: usize = 15;
+
+// 設定画面などで上書きされた境界判定のオプション（None なら既定値）
+static BOUNDARY_RULES: RwLock<Option<BoundaryOptions>> = RwLock::new(None);
+
+// テストでは規則をスレッドごとに上書きし、並行して動く他のテストに影響しないようにする
+#[cfg(test)]
+thread_local! {
+    static TEST_BOUNDARY_RULES: std::cell::RefCell<Option<BoundaryOptions>> = const { std::cell::RefCell::new(None) };
+}
+
+// 現在の境界判定のオプション（上書きされていなければ既定値）
+fn active_boundary_rules() -> BoundaryOptions {
+    #[cfg(test)]
+    if let Some(rules) = TEST_BOUNDARY_RULES.with(|rules| rules.borrow().clone()) {
+        return rules;
     }
+    BOUNDARY_RULES
+        .read()
+        .ok()
+        .and_then(|rules| rules.clone())
+        .unwrap_or_default()
+}
+
+// 現在の境界判定のオプションを JSON で返す
+// 返した JSON はそのまま set_boundary_rules に渡せる
+pub fn get_boundary_rules() -> serde_json::Value {
+    serde_json::to_value(active_boundary_rules()).unwrap_or_default()
+}
+
+// 境界判定のオプションを JSON で上書きする（省略した項目は既定値）
+// 文節を返すコマンドは boundary_vector（常に既定値）を除いてこの規則で区切る
+// - 文節の文字列だけを返すコマンド（split_bunsetsu とその一括・ストリーム版、結果の結合、段落、
+//   読みやすさ、文ごとの解析、文字種）は、文字の正規化と縮約形の展開も含めてすべての項目に従う
+// - 元のテキストのトークンや位置を返すコマンド（split_with_offsets、詳細、trace、confidence、
+//   analyze_and_split、主要語、節、SSML、IOB、係り受け、読み上げ時間、RSVP、印刷用、分割の評価、プレビュー）は
+//   source_options の規則で分け、文字の正規化と縮約形の展開だけは行わない
+// 解釈できない JSON の場合は現在のオプションを変えずにエラーを返す
+pub fn set_boundary_rules(rules: serde_json::Value) -> Result<(), BunsetsuError> {
+    let rules: BoundaryOptions =
+        serde_json::from_value(rules).map_err(|e| BunsetsuError::InvalidArgument(e.to_string()))?;
+    *BOUNDARY_RULES
+        .write()
+        .map_err(|e| BunsetsuError::Internal(e.to_string()))? = Some(rules);
+    Ok(())
 }
 
 // 助詞の詳細な分類と文節境界判定
-fn check_particle_boundary(current: &TokenInfo, next: &TokenInfo) -> bool {
+// prev は助詞の直前のトークン（文頭の場合は None）
+fn check_particle_boundary(
+    prev: Option<&TokenInfo>,
+    current: &TokenInfo,
+    next: &TokenInfo,
+    options: &BoundaryOptions,
+) -> bool {
     let particle_type = current.pos_detail_1().unwrap_or("");
     let particle_text = current.text.as_str();
     let next_pos = next.pos();
 
+    // 節を終える接続助詞に付く終助詞（いいけどね）は、助詞を分ける場合も同じ文節にする
+    // 節の区切りは終助詞の後ろに置く
+    if particle_type == "接続助詞" && next.pos_detail_1() == Some("終助詞") {
+        return false;
+    }
+
+    // 口語の引用の「って」（行くって言った）も「と」と同じく引用の動詞の前で区切らない
+    // IPADIC では格助詞にも副助詞にもなるので、助詞の種類によらず判定する
+    if particle_text == "って" && is_quotative_verb(next) {
+        return false;
+    }
+
+    // 比較の「よりも」は一語の助詞のように働くので、助詞を分ける場合も同じ文節にする
+    if particle_text == "より" && next.pos() == "助詞" && next.text == "も" {
+        return false;
+    }
+
+    // 連続する助詞（では、には、からも など）は一つの読みの単位として結合する
+    if next_pos == "助詞" {
+        return options.split_compound_particles;
+    }
+
     match particle_type {
         "格助詞" => {
             match particle_text {
@@ -85,16 +663,15 @@ fn check_particle_boundary(current: &TokenInfo, next: &TokenInfo) -> bool {
                     matches!(next_pos, "動詞" | "形容詞" | "形容動詞" | "助動詞")
                 }
                 "と" => {
-                    // 引用の「と」は区切らない
-                    if let Some(base) = next.base_form() {
-                        if matches!(base, "いう" | "言う" | "思う" | "考える" | "する" | "なる")
-                        {
-                            return false;
-                        }
+                    // 副詞に付く「と」（ゆっくりと、はっきりと）は副詞と同じ文節にし、後ろで区切る
+                    if prev.is_some_and(|p| p.pos() == "副詞") {
+                        return true;
                     }
-                    true
+                    // 引用の「と」は区切らない
+                    !is_quotative_verb(next)
                 }
-                // その他の格助詞（が、を、に、で、へ、から、まで、より）は区切る
+                // その他の格助詞（が、を、に、で、へ、から、より、まで）は区切る
+                // 目的語の「を」も後ろの動詞と結合しない（勉強を／する）
                 _ => true,
             }
         }
@@ -107,6 +684,14 @@ fn check_particle_boundary(current: &TokenInfo, next: &TokenInfo) -> bool {
             // 基本的に区切るが、一部例外あり
             match particle_text {
                 "て" | "で" => {
+                    // 読点・句点が続く場合はそこまでを同じ文節にする（美しくて、）
+                    if matches!(next.pos_detail_1(), Some("読点" | "句点")) {
+                        return false;
+                    }
+                    // 形容詞に付く「て」は節をつなぐので区切る（高くて／買えない）
+                    if prev.map(|p| p.pos() == "形容詞").unwrap_or(false) {
+                        return true;
+                    }
                     // 「て」「で」は次が補助動詞の場合は区切らない
                     if next_pos == "動詞" {
                         if let Some(detail) = next.pos_detail_1() {
@@ -115,6 +700,9 @@ fn check_particle_boundary(current: &TokenInfo, next: &TokenInfo) -> bool {
                     }
                     next_pos != "助動詞"
                 }
+                // 同時の「ながら」「つつ」は連用形の動詞と同じ文節になる（前の結合は活用形の判定で行う）
+                // 従属節の終わりなので後ろで区切る（歩きながら／話す）
+                "ながら" | "つつ" => true,
                 _ => true,
             }
         }
@@ -125,10 +713,12 @@ fn check_particle_boundary(current: &TokenInfo, next: &TokenInfo) -> bool {
         }
         "連体化" => {
             // 「の」（形式名詞的用法）
-            false
+            // 後ろに用言が続く場合（やるのやらないの）は「の」で区切る
+            matches!(next_pos, "動詞" | "形容詞")
         }
         "並立助詞" => {
-            // や、か、とか など
+            // や、か、とか、たり など
+            // 「〜たりする」は is_light_verb_suru で扱う
             true
         }
         _ => {
@@ -144,11 +734,23 @@ fn check_conjugation_boundary(current: &TokenInfo, next: &TokenInfo) -> bool {
     let conjugation = current.conjugation_form().unwrap_or("");
     let next_pos = next.pos();
 
+    // 伝聞（美味しいそうだ：終止形＋そう）・様態（降りそうだ：連用形＋そう）の「そう」は
+    // IPADIC では名詞（助動詞語幹）になるが、述語の一部として同じ文節にする
+    if is_sou_auxiliary_stem(next) {
+        return false;
+    }
+
+    // 可能の「られる」とら抜きの「れる」（食べられる、食べれる）は、活用形の解析が揺れても
+    // 同じ文節にする
+    if is_potential_suffix(next) {
+        return false;
+    }
+
     match conjugation {
         "終止形" | "基本形" => {
             // 文末なので基本的に区切る
-            // ただし、次が助動詞・助詞の場合は例外
-            !matches!(next_pos, "助動詞" | "助詞")
+            // ただし、次が助動詞・助詞・形式名詞の「の」「ん」（やるの、行くんだ）の場合は例外
+            !matches!(next_pos, "助動詞" | "助詞") && !is_nominalizer(next)
         }
         "連体形" => {
             // 連体修飾なので区切らない
@@ -158,13 +760,12 @@ fn check_conjugation_boundary(current: &TokenInfo, next: &TokenInfo) -> bool {
             // 連用形は文脈による
             match next_pos {
                 "助動詞" => false, // 〜している、〜してある など
+                "助詞" => false,   // 〜て、〜ながら など
                 "動詞" => {
                     // 複合動詞かどうか判定
-                    if let Some(detail) = next.pos_detail_1() {
-                        detail == "非自立" // 補助動詞なら区切らない
-                    } else {
-                        true // 独立した動詞なら区切る
-                    }
+                    // 補助動詞（非自立）と、自立として解析される複合動詞の後ろの要素（始める、出す、込む など）は区切らない
+                    // 独立した動詞なら区切る
+                    next.pos_detail_1() != Some("非自立") && !is_compound_verb_suffix(next)
                 }
                 _ => true,
             }
@@ -181,6 +782,52 @@ fn check_conjugation_boundary(current: &TokenInfo, next: &TokenInfo) -> bool {
     }
 }
 
+// 連用形に付いて複合動詞を作る動詞（読み始める、走り出す、考え込む）
+// IPADIC では自立の動詞として解析されるので、原形で判定する
+fn is_compound_verb_suffix(info: &TokenInfo) -> bool {
+    info.pos() == "動詞"
+        && info.base_form().is_some_and(|base| {
+            matches!(
+                base,
+                "始める"
+                    | "はじめる"
+                    | "終わる"
+                    | "おわる"
+                    | "終える"
+                    | "おえる"
+                    | "続ける"
+                    | "つづける"
+                    | "出す"
+                    | "だす"
+                    | "込む"
+                    | "こむ"
+                    | "直す"
+                    | "なおす"
+                    | "合う"
+                    | "あう"
+                    | "過ぎる"
+                    | "すぎる"
+                    | "切る"
+                    | "きる"
+                    | "忘れる"
+                    | "損なう"
+                    | "そこなう"
+            )
+        })
+}
+
+// 伝聞・様態の助動詞「そうだ」の語幹
+fn is_sou_auxiliary_stem(info: &TokenInfo) -> bool {
+    info.text == "そう" && info.pos() == "名詞" && info.pos_detail_2() == Some("助動詞語幹")
+}
+
+// 形式名詞の「の」（やるの、読むのが）と、説明の「ん」（行くんだ、大変なんです）
+fn is_nominalizer(info: &TokenInfo) -> bool {
+    matches!(info.text.as_str(), "の" | "ん")
+        && info.pos() == "名詞"
+        && info.pos_detail_1() == Some("非自立")
+}
+
 // 助動詞の文節境界判定
 fn check_auxiliary_boundary(current: &TokenInfo, next: &TokenInfo) -> bool {
     let aux_text = current.text.as_str();
@@ -188,19 +835,28 @@ fn check_auxiliary_boundary(current: &TokenInfo, next: &TokenInfo) -> bool {
 
     // 助動詞の種類による判定
     match aux_text {
+        "ある" | "あり" | "あっ" | "あれ" | "あろ" if current.base_form() == Some("ある") =>
+        {
+            // 「である」の活用（学生であり、重要であれば、学生であって、学生であった）は
+            // 前の「で」・名詞と同じ文節にし、後ろの助詞・助動詞（ば・て・た）も含める
+            !matches!(next_pos, "助詞" | "助動詞")
+        }
         "いる" | "ある" | "おる" => {
             // 補助動詞的な助動詞
             matches!(next_pos, "助詞" | "記号")
         }
-        "ない" | "ぬ" | "ん" => {
-            // 否定の助動詞
+        _ if matches!(current.base_form(), Some("ない" | "ぬ" | "ん")) => {
+            // 否定の助動詞（活用した「なく」「なかっ」も含む）
             if next_pos == "助詞" {
-                // 「〜ないで」「〜ないから」など
-                if let Some(particle) = next.text.as_str().chars().next() {
-                    matches!(particle, 'で' | 'か' | 'の')
-                } else {
-                    true
+                // 接続助詞の「て」「で」（食べなくて、行かないで）は同じ文節にする
+                if next.pos_detail_1() == Some("接続助詞")
+                    && matches!(next.base_form(), Some("て" | "で"))
+                {
+                    return false;
                 }
+                // 勧誘の「〜ませんか」も「ません」までを述語の文節にし、終助詞「か」で区切る（始めましょう／か と同じ）
+                // 「〜ないの」の「の」も区切る
+                matches!(next.base_form(), Some("か" | "の"))
             } else {
                 matches!(next_pos, "記号")
             }
@@ -213,6 +869,26 @@ fn check_auxiliary_boundary(current: &TokenInfo, next: &TokenInfo) -> bool {
             // 受身・使役の助動詞
             !matches!(next_pos, "助動詞")
         }
+        "で" if current.base_form() == Some("だ") => {
+            // 断定の「だ」の連用形（静かで、学生で）は前の語に付き、節をつなぐので後ろで区切る
+            // 格助詞の「で」（東京で）・接続助詞の「で」（読んで）は助詞の判定で扱う
+            // 「静かではない」「静かである」のように打消し・補助の「ある」が続く場合は区切らない
+            !matches!(next_pos, "助詞" | "助動詞") && next.base_form() != Some("ある")
+        }
+        "な" if current.base_form() == Some("だ") => {
+            // 断定の「だ」の連体形（静かなので、好きなのに）は後ろの「ので・のに・の」と同じ文節にする
+            !next.text.starts_with('の') && matches!(next_pos, "助詞" | "記号")
+        }
+        "う" | "よう" | "ましょ" => {
+            // 意志・勧誘の助動詞（帰ろう、食べよう、始めましょう）は述語に付ける
+            // 「ましょ」の後ろの「う」も同じ文節にし、句読点・助詞の前でのみ区切る
+            matches!(next_pos, "助詞" | "記号")
+        }
+        "まい" => {
+            // 打消しの意志・推量の「まい」（二度と行くまい）も述語に付ける
+            // 「行くまいと思う」「行くまいが」のように助詞が続く場合も同じ文節にし、句読点・終助詞の前でのみ区切る
+            next_pos == "記号" || next.pos_detail_1() == Some("終助詞")
+        }
         _ => {
             // その他の助動詞
             matches!(next_pos, "助詞" | "記号")
@@ -220,11 +896,152 @@ fn check_auxiliary_boundary(current: &TokenInfo, next: &TokenInfo) -> bool {
     }
 }
 
+// 英数字に付く記号（50％、Q＆A、info＠example）は語の一部として扱う
+fn is_word_internal_symbol(current: &TokenInfo, next: &TokenInfo) -> bool {
+    let ends_with_alnum = current.text.chars().last().is_some_and(is_latin_or_digit);
+    let starts_with_alnum = next.text.chars().next().is_some_and(is_latin_or_digit);
+
+    // 記号の前：直前が英数字なら結合（50％ / Q＆）
+    if matches!(next.text.as_str(), "％" | "%" | "＆" | "&" | "＠" | "@") && ends_with_alnum {
+        return true;
+    }
+    // 記号の後：＆・＠ の後ろが英数字なら結合（＆A）
+    matches!(current.text.as_str(), "＆" | "&" | "＠" | "@") && starts_with_alnum
+}
+
+// 数字だけからなるトークン（算用数字・漢数字）
+fn is_numeric_token(info: &TokenInfo) -> bool {
+    info.pos_detail_1() == Some("数")
+        || (!info.text.is_empty()
+            && info
+                .text
+                .chars()
+                .all(|c| c.is_ascii_digit() || matches!(c, '０'..='９')))
+}
+
+// 範囲（3〜5）・分数（2/3）・時刻（3：00）をつなぐ記号と、桁区切り・小数点（1,000、3.5）
+fn is_numeric_connector(info: &TokenInfo) -> bool {
+    matches!(
+        info.text.as_str(),
+        "〜" | "～" | "-" | "－" | "/" | "／" | ":" | "：" | "," | "，" | "." | "．"
+    )
+}
+
+// 助数詞（3時間の「時間」、5個の「個」、三冊の「冊」）
+fn is_counter_suffix(info: &TokenInfo) -> bool {
+    info.pos_detail_2() == Some("助数詞")
+}
+
+// 数と助数詞の並び（5個、千二百三十円、12時30分）は一つの文節にする
+fn is_quantity_continuation(current: &TokenInfo, next: &TokenInfo) -> bool {
+    // 数 → 数（千／二／百／三／十）、数 → 助数詞
+    (is_numeric_token(current) && (is_numeric_token(next) || is_counter_suffix(next)))
+        // 助数詞 → 数（12時／30分）
+        || (is_counter_suffix(current) && is_numeric_token(next))
+}
+
+// 数字と接続記号の並び（3〜5人、2/3、10:30）は一つの文節にする
+fn is_numeric_expression(prev: Option<&TokenInfo>, current: &TokenInfo, next: &TokenInfo) -> bool {
+    // 数字 → 接続記号
+    if is_numeric_token(current) && is_numeric_connector(next) {
+        return true;
+    }
+    // 接続記号 → 数字（接続記号の前も数字の場合）
+    is_numeric_connector(current) && is_numeric_token(next) && prev.is_some_and(is_numeric_token)
+}
+
+// 引用の「と」「って」を受ける動詞（言う、思う など）
+fn is_quotative_verb(info: &TokenInfo) -> bool {
+    info.base_form()
+        .is_some_and(|base| matches!(base, "いう" | "言う" | "思う" | "考える" | "する" | "なる"))
+}
+
+// 「する」（UniDic では原形が「為る」）
+fn is_suru(info: &TokenInfo) -> bool {
+    info.pos() == "動詞" && matches!(info.base_form(), Some("する" | "為る"))
+}
+
+// 後ろの「する」と合わせて一つの動詞のように働く語
+// サ変名詞・一般名詞（勉強する、テニスする）、擬態語などの副詞（ゆっくりする、はっきりする）、
+// 「〜たり」の列挙を締めくくる「する」（読んだり／書いたりする）
+// 「する」以降の活用（勉強しない、勉強します、勉強させる）は用言・助動詞の判定で同じ文節になる
+fn is_light_verb_suru(current: &TokenInfo, next: &TokenInfo) -> bool {
+    if !is_suru(next) {
+        return false;
+    }
+    match current.pos() {
+        "名詞" => !is_nominalizer(current) && current.pos_detail_1() != Some("代名詞"),
+        "副詞" => current.pos_detail_1() == Some("助詞類接続"),
+        "助詞" => matches!(current.text.as_str(), "たり" | "だり"),
+        _ => false,
+    }
+}
+
+// 名詞に付いて接尾辞のように働く語（だらけ・まみれ・ずくめ）
+fn is_suffix_like_noun(info: &TokenInfo) -> bool {
+    info.pos() == "名詞" && matches!(info.text.as_str(), "だらけ" | "まみれ" | "ずくめ")
+}
+
+// 可能・受身の「れる」「られる」（動詞・接尾）
+// ら抜き言葉（食べれる）の「れる」も同じく接尾として現れる
+fn is_potential_suffix(info: &TokenInfo) -> bool {
+    info.pos() == "動詞"
+        && info.pos_detail_1() == Some("接尾")
+        && matches!(info.base_form(), Some("れる" | "られる"))
+}
+
+// ら抜き言葉：一段動詞・カ変動詞に「られる」ではなく「れる」が付いたもの（食べれる、来れる）
+// 五段動詞の「れる」（書かれる、行ける）は標準の形なので含めない
+fn is_ra_nuki(current: &TokenInfo, next: &TokenInfo) -> bool {
+    current.pos() == "動詞"
+        && current
+            .conjugation_type()
+            .is_some_and(|kind| kind.starts_with("一段") || kind.starts_with("カ変"))
+        && is_potential_suffix(next)
+        && next.base_form() == Some("れる")
+}
+
+// 半角・全角の英字と数字
+fn is_latin_or_digit(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '０'..='９' | 'Ａ'..='Ｚ' | 'ａ'..='ｚ')
+}
+
 // 文節境界を判定するメイン関数
-fn is_bunsetsu_boundary(current: &TokenInfo, next: &TokenInfo) -> bool {
+fn is_bunsetsu_boundary(
+    prev: Option<&TokenInfo>,
+    current: &TokenInfo,
+    next: &TokenInfo,
+    options: &BoundaryOptions,
+) -> bool {
     let curr_pos = current.pos();
     let next_pos = next.pos();
 
+    // 語の一部になる記号は前後と結合
+    if is_word_internal_symbol(current, next) {
+        return false;
+    }
+
+    // 数値の範囲・分数・時刻は一つの文節にする
+    if is_numeric_expression(prev, current, next) {
+        return false;
+    }
+
+    // 数と助数詞は一つの文節にする
+    if is_quantity_continuation(current, next) {
+        return false;
+    }
+
+    // 接尾辞のように働く語（泥だらけ、ほこりまみれ）は前の語に付ける
+    // 「間違い」が動詞の連用形として解析される場合もあるので、前の語の品詞によらず結合する
+    if is_suffix_like_noun(next) && curr_pos != "記号" {
+        return false;
+    }
+
+    // 名詞・副詞などに付く「する」は同じ文節にする（勉強する、ゆっくりする）
+    if is_light_verb_suru(current, next) {
+        return false;
+    }
+
     // 記号の処理
     if curr_pos == "記号" {
         match current.text.as_str() {
@@ -237,17 +1054,20 @@ fn is_bunsetsu_boundary(current: &TokenInfo, next: &TokenInfo) -> bool {
 
     // 品詞別の詳細な判定
     match curr_pos {
-        "助詞" => check_particle_boundary(current, next),
+        "助詞" => check_particle_boundary(prev, current, next, options),
         "動詞" | "形容詞" | "形容動詞" => check_conjugation_boundary(current, next),
         "助動詞" => check_auxiliary_boundary(current, next),
-        "接続詞" => true,  // 接続詞は独立した文節
-        "感動詞" => true,  // 感動詞も独立
+        // 接続詞・感動詞は独立した文節。直後の読点は同じ文節に含める（しかし、／はい、）
+        "接続詞" | "感動詞" => next.pos_detail_1() != Some("読点"),
         "接頭詞" => false, // 接頭詞は次と結合
         "名詞" => {
             // 名詞の後の処理
             match next_pos {
                 "助詞" => false,   // 名詞＋助詞は一つの文節
                 "接尾詞" => false, // 名詞＋接尾詞も結合
+                // IPADIC の接尾辞は名詞・接尾として現れる（会議中・食事後の 中・後 は 接尾・副詞可能、泥だらけ の だらけ は 接尾・一般）
+                // 固有名詞かどうかに関わらず前の名詞に付け、後ろの助詞もそのまま同じ文節にする
+                "名詞" if next.pos_detail_1() == Some("接尾") => false,
                 "名詞" => {
                     // 複合名詞の判定
                     if let Some(detail) = current.pos_detail_1() {
@@ -265,6 +1085,8 @@ fn is_bunsetsu_boundary(current: &TokenInfo, next: &TokenInfo) -> bool {
                         false
                     }
                 }
+                // 「の」の後ろに用言が続く繰り返し（やるのやらないの）は「の」ごとに区切る
+                "動詞" | "形容詞" if is_nominalizer(current) => true,
                 _ => false,
             }
         }
@@ -274,97 +1096,5553 @@ fn is_bunsetsu_boundary(current: &TokenInfo, next: &TokenInfo) -> bool {
         }
         "連体詞" => {
             // 連体詞は次の名詞と結合
-            false
+            // オプション指定時は、指示語（この・その・あの・どの）の後ろで区切る
+            options.split_demonstratives
+                && matches!(current.text.as_str(), "この" | "その" | "あの" | "どの")
         }
         _ => false,
     }
 }
 
-pub fn split_text_into_bunsetsu(text: String) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-    eprintln!("入力テキスト: {}", text);
+// 境界判定の確からしさ（0.0〜1.0）
+// 文法的に明確な区切りは 1.0、経験則による曖昧な区切りは低めにする
+fn boundary_confidence(current: &TokenInfo, next: &TokenInfo) -> f64 {
+    match current.pos() {
+        // 句読点・括弧による区切り
+        "記号" => 1.0,
+        "接続詞" | "感動詞" => 1.0,
+        "助詞" => match current.pos_detail_1().unwrap_or("") {
+            // 「の」の後ろで区切るのは連体修飾か主格かが曖昧な場合
+            "格助詞" if current.text == "の" => 0.4,
+            // 引用の「と」との区別が曖昧
+            "格助詞" if current.text == "と" => 0.6,
+            "格助詞" | "係助詞" | "副助詞" | "終助詞" => 0.9,
+            "接続助詞" | "並立助詞" => 0.8,
+            _ => 0.6,
+        },
+        // 活用形による判定は文脈に左右される
+        "動詞" | "形容詞" | "形容動詞" | "助動詞" => 0.7,
+        // 固有名詞の後など
+        "名詞" if next.pos() == "名詞" => 0.5,
+        _ => 0.6,
+    }
+}
+
+// テキストを形態素解析してトークン情報の列にする
+// 結果は解析結果のキャッシュと共有する
+fn tokenize_to_infos(text: &str) -> Result<Arc<Vec<TokenInfo>>, BunsetsuError> {
+    let loaded = get_tokenizer().map_err(|e| BunsetsuError::TokenizerInit(e.to_string()))?;
+    tokenize_cached(&loaded, text)
+}
+
+// 共有のトークナイザでの解析結果を入力テキストごとに保持する LRU キャッシュ
+// 編集中に同じ段落を何度も解析し直さないため
+struct TokenLru {
+    capacity: usize,
+    // 古い順（末尾が最近使ったもの）
+    entries: VecDeque<(String, Arc<Vec<TokenInfo>>)>,
+    hits: u64,
+}
+
+impl TokenLru {
+    fn get(&mut self, text: &str) -> Option<Arc<Vec<TokenInfo>>> {
+        let index = self.entries.iter().position(|(key, _)| key == text)?;
+        let entry = self.entries.remove(index)?;
+        let token_infos = Arc::clone(&entry.1);
+        self.entries.push_back(entry);
+        self.hits += 1;
+        Some(token_infos)
+    }
+
+    fn insert(&mut self, text: &str, token_infos: Arc<Vec<TokenInfo>>) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.entries.retain(|(key, _)| key != text);
+        while self.entries.len() >= self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((text.to_string(), token_infos));
+    }
+}
+
+// 既定で保持するテキストの数
+const DEFAULT_TOKEN_LRU_CAPACITY: usize = 32;
 
-    let tokenizer = get_tokenizer();
-    let mut tokens = tokenizer.tokenize(&text)?;
+static TOKEN_LRU: Mutex<TokenLru> = Mutex::new(TokenLru {
+    capacity: DEFAULT_TOKEN_LRU_CAPACITY,
+    entries: VecDeque::new(),
+    hits: 0,
+});
+
+// 保持するテキストの数を変える（0 ならキャッシュしない）。あふれた分は古いものから捨てる
+pub fn set_cache_capacity(capacity: usize) {
+    if let Ok(mut lru) = TOKEN_LRU.lock() {
+        lru.capacity = capacity;
+        while lru.entries.len() > capacity {
+            lru.entries.pop_front();
+        }
+    }
+}
+
+// 保持している解析結果をすべて捨てる
+pub fn clear_cache() {
+    if let Ok(mut lru) = TOKEN_LRU.lock() {
+        lru.entries.clear();
+    }
+}
+
+// キャッシュから解析結果を返した回数
+pub fn cache_hits() -> u64 {
+    TOKEN_LRU.lock().map(|lru| lru.hits).unwrap_or(0)
+}
+
+// 共有のトークナイザで解析する（同じテキストはキャッシュから返す）
+fn tokenize_cached(
+    loaded: &LoadedTokenizer,
+    text: &str,
+) -> Result<Arc<Vec<TokenInfo>>, BunsetsuError> {
+    if let Some(token_infos) = TOKEN_LRU.lock().ok().and_then(|mut lru| lru.get(text)) {
+        return Ok(token_infos);
+    }
+
+    let token_infos =
+        Arc::new(tokenize_with(loaded, text).map_err(|e| BunsetsuError::Tokenize(e.to_string()))?);
+    if let Ok(mut lru) = TOKEN_LRU.lock() {
+        lru.insert(text, Arc::clone(&token_infos));
+    }
+    Ok(token_infos)
+}
+
+fn tokenize_with(
+    loaded: &LoadedTokenizer,
+    text: &str,
+) -> Result<Vec<TokenInfo>, Box<dyn std::error::Error>> {
+    let layout = FeatureLayout::for_kind(&loaded.kind);
+    let mut tokens = loaded.tokenizer().tokenize(text)?;
 
     // トークンから情報を抽出
     let mut token_infos = Vec::new();
-    eprintln!("\n--- トークン情報 ---");
+    debug_log!("\n--- トークン情報 ---");
     for (i, token) in tokens.iter_mut().enumerate() {
         let features: Vec<String> = token.details().iter().map(|s| s.to_string()).collect();
         let token_info = TokenInfo {
             text: token.text.to_string(),
             features: features.clone(),
+            layout,
+            byte_start: token.byte_start,
         };
 
         // 簡潔なログ出力（v2形式）
-        eprint!("[{}]「{}」{}・", i, token_info.text, token_info.pos());
-        if let Some(detail) = token_info.pos_detail_1() {
-            eprint!("{}", detail);
-        }
-        if token_info.pos() == "動詞" || token_info.pos() == "形容詞" {
-            if let Some(conj) = token_info.conjugation_form() {
-                eprint!("・{}", conj);
+        if debug_logging_enabled() {
+            let mut line = format!("[{}]「{}」{}・", i, token_info.text, token_info.pos());
+            if let Some(detail) = token_info.pos_detail_1() {
+                line.push_str(detail);
             }
+            if token_info.pos() == "動詞" || token_info.pos() == "形容詞" {
+                if let Some(conj) = token_info.conjugation_form() {
+                    line.push('・');
+                    line.push_str(conj);
+                }
+            }
+            debug_log!("{}", line);
         }
-        eprintln!();
 
         token_infos.push(token_info);
     }
 
-    let mut phrases = Vec::new();
-    let mut current_phrase = String::new();
-
-    for i in 0..token_infos.len() {
-        let info = &token_infos[i];
-        current_phrase.push_str(&info.text);
-
-        // 次のトークンがある場合、文節境界を判定
-        if i < token_infos.len() - 1 {
-            let next_info = &token_infos[i + 1];
+    Ok(token_infos)
+}
 
-            let is_boundary = is_bunsetsu_boundary(info, next_info);
-            eprintln!(
-                "境界判定: \"{}\" -> \"{}\" = {}",
-                info.text, next_info.text, is_boundary
-            );
+// 隣り合うトークン間ごとの境界判定結果（トークン数 - 1 個）
+fn compute_boundaries(token_infos: &[TokenInfo], options: &BoundaryOptions) -> Vec<bool> {
+    let mut boundaries = if options.strategy == SplitStrategy::Simple {
+        simple_boundaries(token_infos)
+    } else {
+        rule_based_boundaries(token_infos, options)
+    };
 
-            if is_boundary {
-                if !current_phrase.is_empty() {
-                    eprintln!("文節確定: \"{}\"", current_phrase);
-                    phrases.push(current_phrase.clone());
-                    current_phrase.clear();
-                }
+    if options.quote_mode == QuoteMode::KeepShort {
+        for span in quote_spans(token_infos) {
+            let chars: usize = token_infos[span.clone()]
+                .iter()
+                .map(|info| info.text.chars().count())
+                .sum();
+            if chars <= SHORT_QUOTE_MAX_CHARS {
+                // 閉じ括弧の後ろの境界はそのまま残す
+                boundaries[span.start..span.end - 1].fill(false);
             }
         }
     }
 
-    // 最後の文節を追加
-    if !current_phrase.is_empty() {
-        eprintln!("最後の文節: \"{}\"", current_phrase);
-        phrases.push(current_phrase);
+    // 入力の末尾に閉じられない開き括弧だけが残った場合は、直前の文節に含める
+    let trailing_openers = token_infos
+        .iter()
+        .rev()
+        .take_while(|info| is_opening_quote(&info.text))
+        .count();
+    if trailing_openers > 0 && trailing_openers < token_infos.len() {
+        boundaries[token_infos.len() - trailing_openers - 1] = false;
     }
 
-    eprintln!("\n最終結果: {:?}", phrases);
+    boundaries
+}
 
-    Ok(phrases)
+fn is_opening_quote(text: &str) -> bool {
+    matches!(text, "「" | "『")
 }
 
-// テスト用のmain関数（必要に応じてコメントアウトまたは削除）
-#[cfg(test)]
-mod tests {
-    use super::*;
+// 開き括弧に対応する閉じ括弧
+fn closing_quote(open: &str) -> &'static str {
+    if open == "『" {
+        "』"
+    } else {
+        "」"
+    }
+}
 
-    #[test]
-    fn test_bunsetsu_split() -> Result<(), Box<dyn std::error::Error>> {
-        let text = "人間は文章を読む時、滑らかに文字を読んでいる訳ではなく、「１点を見つめる」という事と「高速に視線を移動する」という事を繰り返しています。".to_string();
-        let bunsetsu = split_text_into_bunsetsu(text)?;
+// 対応の取れた引用の範囲（開き括弧から閉じ括弧まで）を返す
+// 入れ子の引用（「『本当に』と言った」）は一番外側の範囲だけを返し、閉じられない括弧は無視する
+fn quote_spans(token_infos: &[TokenInfo]) -> Vec<Range<usize>> {
+    let mut spans = Vec::new();
+    let mut stack: Vec<(usize, &str)> = Vec::new();
 
-        for b in &bunsetsu {
-            print!("{} / ", b);
+    for (i, info) in token_infos.iter().enumerate() {
+        if is_opening_quote(&info.text) {
+            stack.push((i, closing_quote(&info.text)));
+            continue;
         }
-        println!();
 
-        assert!(!bunsetsu.is_empty());
-        Ok(())
+        // 対応する開き括弧まで戻る（間の閉じられない開き括弧は捨てる）
+        let Some(depth) = stack.iter().rposition(|(_, close)| *close == info.text) else {
+            continue;
+        };
+        let (start, _) = stack[depth];
+        stack.truncate(depth);
+        if stack.is_empty() {
+            spans.push(start..i + 1);
+        }
+    }
+
+    spans
+}
+
+// 品詞・活用形ごとの規則で文節境界を判定する
+fn rule_based_boundaries(token_infos: &[TokenInfo], options: &BoundaryOptions) -> Vec<bool> {
+    let mut boundaries = Vec::new();
+
+    for i in 0..token_infos.len().saturating_sub(1) {
+        let info = &token_infos[i];
+        let next_info = &token_infos[i + 1];
+
+        let prev_info = if i > 0 { token_infos.get(i - 1) } else { None };
+        let is_boundary = is_bunsetsu_boundary(prev_info, info, next_info, options);
+        debug_log!(
+            "境界判定: \"{}\" -> \"{}\" = {}",
+            info.text,
+            next_info.text,
+            is_boundary
+        );
+
+        boundaries.push(is_boundary);
+    }
+
+    if let Some(limit) = options.auxiliary_chain_limit {
+        insert_auxiliary_chain_breaks(token_infos, &mut boundaries, limit);
+    }
+
+    boundaries
+}
+
+// 述語に続いて連なる語（助動詞、させる・られる などの接尾の動詞）
+fn is_chain_auxiliary(info: &TokenInfo) -> bool {
+    info.pos() == "助動詞"
+        || (info.pos() == "動詞" && matches!(info.pos_detail_1(), Some("接尾" | "非自立")))
+}
+
+// 長い助動詞の連なりを区切るときに、その前で区切ってよい語
+fn is_auxiliary_chain_break_point(info: &TokenInfo) -> bool {
+    info.pos() == "助動詞" && matches!(info.base_form(), Some("た" | "ます" | "たい" | "ない"))
+}
+
+// 助動詞の連なりが limit 個に達したら、次の区切りやすい位置に境界を一つだけ追加する
+fn insert_auxiliary_chain_breaks(token_infos: &[TokenInfo], boundaries: &mut [bool], limit: usize) {
+    let mut chain = 0;
+    let mut inserted = false;
+
+    for (i, boundary) in boundaries.iter_mut().enumerate() {
+        if *boundary {
+            chain = 0;
+            inserted = false;
+            continue;
+        }
+
+        chain = if is_chain_auxiliary(&token_infos[i]) {
+            chain + 1
+        } else {
+            0
+        };
+        if !inserted && chain >= limit && is_auxiliary_chain_break_point(&token_infos[i + 1]) {
+            *boundary = true;
+            chain = 0;
+            inserted = true;
+        }
+    }
+}
+
+// 簡易な判定方法での境界（トークン数 - 1 個）
+// 文節に内容語と助詞・助動詞がそろった後に内容語が来たら区切る。句読点の後ろと、
+// 文節が SIMPLE_MAX_PHRASE_CHARS 文字に達した場合も区切る
+fn simple_boundaries(token_infos: &[TokenInfo]) -> Vec<bool> {
+    let mut boundaries = Vec::new();
+    let mut has_content_word = false;
+    let mut has_particle = false;
+    let mut length = 0;
+
+    for pair in token_infos.windows(2) {
+        let (info, next) = (&pair[0], &pair[1]);
+        if is_content_word(info) {
+            has_content_word = true;
+        } else if matches!(info.pos(), "助詞" | "助動詞") {
+            has_particle = true;
+        }
+        length += info.text.chars().count();
+
+        let is_boundary = matches!(info.pos_detail_1(), Some("読点" | "句点"))
+            || (has_content_word && has_particle && is_content_word(next))
+            || length >= SIMPLE_MAX_PHRASE_CHARS;
+        debug_log!(
+            "境界判定（簡易）: \"{}\" -> \"{}\" = {}",
+            info.text,
+            next.text,
+            is_boundary
+        );
+
+        if is_boundary {
+            has_content_word = false;
+            has_particle = false;
+            length = 0;
+        }
+        boundaries.push(is_boundary);
+    }
+
+    boundaries
+}
+
+// トークン列を文節に分け、各文節を構成するトークンの範囲を返す
+fn segment_token_infos(token_infos: &[TokenInfo], options: &BoundaryOptions) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+
+    for (i, is_boundary) in compute_boundaries(token_infos, options)
+        .into_iter()
+        .enumerate()
+    {
+        if is_boundary && push_phrase_range(&mut ranges, token_infos, start..i + 1) {
+            start = i + 1;
+        }
+    }
+
+    // 最後の文節を追加
+    push_phrase_range(&mut ranges, token_infos, start..token_infos.len());
+
+    ranges
+}
+
+// 文節の範囲を追加する（文節はすべてここを通して追加する）
+// 空の文節は追加しない。空白だけの文節は直前の文節に含め、直前がなければ次の文節に含める
+// 範囲を使い切った（追加した・直前に含めた）場合は true を返す
+fn push_phrase_range(
+    ranges: &mut Vec<Range<usize>>,
+    token_infos: &[TokenInfo],
+    range: Range<usize>,
+) -> bool {
+    if range.is_empty() {
+        return false;
+    }
+
+    let phrase = join_token_texts(&token_infos[range.clone()]);
+    if phrase.trim().is_empty() {
+        return match ranges.last_mut() {
+            Some(last) if last.end == range.start => {
+                last.end = range.end;
+                true
+            }
+            _ => false,
+        };
+    }
+
+    debug_log!("文節確定: \"{}\"", phrase);
+    ranges.push(range);
+    true
+}
+
+fn join_token_texts(token_infos: &[TokenInfo]) -> String {
+    token_infos.iter().map(|info| info.text.as_str()).collect()
+}
+
+// 空のテキストは EmptyInput にする
+pub fn split_text_into_bunsetsu(text: String) -> Result<Vec<String>, BunsetsuError> {
+    if text.trim().is_empty() {
+        return Err(BunsetsuError::EmptyInput);
+    }
+    split_phrases(text)
+}
+
+// 空のテキストも受け付ける（文節は空になる）。他の解析の途中で文節に分けるときに使う
+fn split_phrases(text: String) -> Result<Vec<String>, BunsetsuError> {
+    split_text_into_bunsetsu_with_options(text, &active_boundary_rules())
+}
+
+pub fn split_text_into_bunsetsu_with_options(
+    text: String,
+    options: &BoundaryOptions,
+) -> Result<Vec<String>, BunsetsuError> {
+    let loaded = get_tokenizer().map_err(|e| BunsetsuError::TokenizerInit(e.to_string()))?;
+    split_phrases_with(&loaded, text, options)
+}
+
+// 取得済みのトークナイザで文節に分ける（まとめて分けるときにトークナイザを取り直さない）
+fn split_phrases_with(
+    loaded: &LoadedTokenizer,
+    text: String,
+    options: &BoundaryOptions,
+) -> Result<Vec<String>, BunsetsuError> {
+    debug_log!("入力テキスト: {}", text);
+
+    let phrases = segment_text_with(loaded, text, options, join_token_texts)?;
+
+    debug_log!("\n最終結果: {:?}", phrases);
+
+    Ok(phrases)
+}
+
+// 解析前の前処理（文字の正規化 → 縮約形の展開）。文節に分ける処理はすべてここを通す
+fn preprocess_text(text: String, options: &BoundaryOptions) -> Result<String, BunsetsuError> {
+    let text = match options.normalize {
+        Some(normalize) => normalize_text(&text, normalize),
+        None => text,
+    };
+    if options.expand_contractions {
+        expand_contractions(&text)
+    } else {
+        Ok(text)
+    }
+}
+
+// テキストを文節に分け、文節ごとにそのトークン列を each に渡した結果を返す
+fn segment_text_with<T>(
+    loaded: &LoadedTokenizer,
+    text: String,
+    options: &BoundaryOptions,
+    each: impl FnMut(&[TokenInfo]) -> T,
+) -> Result<Vec<T>, BunsetsuError> {
+    let text = preprocess_text(text, options)?;
+    let (token_infos, ranges) = segment_tokens(loaded, &text, options)?;
+    Ok(ranges
+        .into_iter()
+        .map(|range| &token_infos[range])
+        .map(each)
+        .collect())
+}
+
+// トークン列と、各文節を構成するトークンの範囲
+type Segments = (Arc<Vec<TokenInfo>>, Vec<Range<usize>>);
+
+// 前処理済みのテキストを文節に分け、トークン列と各文節を構成するトークンの範囲を返す
+// 文ごとに分ける場合は、文ごとに解析したトークン列をつなげて返す
+fn segment_tokens(
+    loaded: &LoadedTokenizer,
+    text: &str,
+    options: &BoundaryOptions,
+) -> Result<Segments, BunsetsuError> {
+    if !options.split_by_sentence {
+        let token_infos = tokenize_cached(loaded, text)?;
+        let ranges = segment_token_infos(&token_infos, options);
+        return Ok((token_infos, ranges));
+    }
+
+    let mut token_infos: Vec<TokenInfo> = Vec::new();
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for sentence in sentence_ranges(text) {
+        let sentence_tokens = tokenize_cached(loaded, &text[sentence.clone()])?;
+        let base = token_infos.len();
+        ranges.extend(
+            segment_token_infos(&sentence_tokens, options)
+                .into_iter()
+                .map(|range| base + range.start..base + range.end),
+        );
+        // 位置は文の中ではなくテキスト全体での位置にする
+        token_infos.extend(sentence_tokens.iter().map(|info| TokenInfo {
+            byte_start: sentence.start + info.byte_start,
+            ..info.clone()
+        }));
+    }
+
+    // 空白だけの文（空行など）は直前の文節に含め、テキストの先頭なら最初の文節に含める
+    for i in 1..ranges.len() {
+        ranges[i - 1].end = ranges[i].start;
+    }
+    if let Some(first) = ranges.first_mut() {
+        first.start = 0;
+    }
+    if let Some(last) = ranges.last_mut() {
+        last.end = token_infos.len();
+    }
+    Ok((Arc::new(token_infos), ranges))
+}
+
+// 表記を変える前処理（文字の正規化と縮約形の展開）を除いた規則
+fn source_options(rules: &BoundaryOptions) -> BoundaryOptions {
+    BoundaryOptions {
+        normalize: None,
+        expand_contractions: false,
+        ..rules.clone()
+    }
+}
+
+// 元の表記のまま現在の規則で文節に分け、トークン列と各文節のトークンの範囲を返す
+// 元のテキストのトークンや位置を返すコマンドはこれを使う
+fn source_segments(text: &str) -> Result<Segments, BunsetsuError> {
+    let loaded = get_tokenizer().map_err(|e| BunsetsuError::TokenizerInit(e.to_string()))?;
+    segment_tokens(&loaded, text, &source_options(&active_boundary_rules()))
+}
+
+// 文節を一つずつ送るときのメッセージ
+// 文節ごとに {"event":"phrase","data":{"index":0,"text":"本を"}} を送り、
+// 最後に {"event":"done","data":{"count":2}} を一度だけ送る
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+pub enum PhraseEvent {
+    Phrase { index: usize, text: String },
+    Done { count: usize },
+}
+
+// 文ごとに文節に分け、できた文節から順に emit に渡す（長い文書でも結果をまとめて保持しない）
+// 区切りは split_by_sentence を有効にした split_text_into_bunsetsu_with_options と同じ
+pub fn stream_text_phrases(
+    text: String,
+    emit: impl FnMut(PhraseEvent) -> Result<(), BunsetsuError>,
+) -> Result<(), BunsetsuError> {
+    let options = BoundaryOptions {
+        split_by_sentence: true,
+        ..active_boundary_rules()
+    };
+    stream_phrases_with(text, &options, emit)
+}
+
+fn stream_phrases_with(
+    text: String,
+    options: &BoundaryOptions,
+    mut emit: impl FnMut(PhraseEvent) -> Result<(), BunsetsuError>,
+) -> Result<(), BunsetsuError> {
+    let text = preprocess_text(text, options)?;
+
+    // 空白だけの文は直前の文節に含めるので（segment_tokens と同じ）、文節は一つ遅れて送る
+    let mut pending: Option<String> = None;
+    let mut leading = String::new();
+    let mut count = 0;
+    for sentence in sentence_ranges(&text) {
+        let sentence = &text[sentence];
+        let token_infos = tokenize_to_infos(sentence)?;
+        let ranges = segment_token_infos(&token_infos, options);
+        if ranges.is_empty() {
+            match pending.as_mut() {
+                Some(phrase) => phrase.push_str(sentence),
+                None => leading.push_str(sentence),
+            }
+            continue;
+        }
+        for range in ranges {
+            let phrase = format!(
+                "{}{}",
+                std::mem::take(&mut leading),
+                join_token_texts(&token_infos[range])
+            );
+            if let Some(text) = pending.replace(phrase) {
+                emit(PhraseEvent::Phrase { index: count, text })?;
+                count += 1;
+            }
+        }
+    }
+    if let Some(text) = pending {
+        emit(PhraseEvent::Phrase { index: count, text })?;
+        count += 1;
+    }
+    emit(PhraseEvent::Done { count })
+}
+
+// 文節と、元のテキストでの位置（文字単位の開始・終了）を返す（文節単位の編集を元の文書に書き戻す用）
+// 位置は隙間なく並び、テキスト全体を覆う
+pub fn split_text_with_offsets(text: String) -> Result<Vec<(String, usize, usize)>, BunsetsuError> {
+    offset_phrases(text, &active_boundary_rules())
+}
+
+// 文節の表記が元のテキストと一致するよう、文字の正規化と縮約形の展開は行わない
+fn offset_phrases(
+    text: String,
+    rules: &BoundaryOptions,
+) -> Result<Vec<(String, usize, usize)>, BunsetsuError> {
+    let loaded = get_tokenizer().map_err(|e| BunsetsuError::TokenizerInit(e.to_string()))?;
+    let (token_infos, ranges) = segment_tokens(&loaded, &text, &source_options(rules))?;
+
+    // 各文節の開始位置（バイト単位）は最初のトークンの位置から求め、文節の間の空行なども含めて
+    // 次の文節の開始位置まで（最後の文節はテキストの末尾まで）を一つの文節とする
+    let mut starts: Vec<usize> = ranges
+        .iter()
+        .map(|range| token_infos[range.start].byte_start)
+        .collect();
+    if let Some(first) = starts.first_mut() {
+        *first = 0;
+    }
+    let mut char_start = 0;
+    let mut phrases = Vec::new();
+    for (i, &start) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).copied().unwrap_or(text.len());
+        let phrase = &text[start..end];
+        let char_end = char_start + phrase.chars().count();
+        phrases.push((phrase.to_string(), char_start, char_end));
+        char_start = char_end;
+    }
+
+    Ok(phrases)
+}
+
+fn word_infos(token_infos: &[TokenInfo]) -> Vec<WordInfo> {
+    token_infos
+        .iter()
+        .map(|info| WordInfo {
+            text: info.text.clone(),
+            pos: info.pos().to_string(),
+            reading: word_reading(info),
+            base_form: word_base_form(info),
+            category: is_katakana_loanword(info).then(|| "loanword".to_string()),
+        })
+        .collect()
+}
+
+// 辞書に原形がない（「*」や素性の欠けた未知語）場合は表記を使う
+fn word_base_form(info: &TokenInfo) -> Option<String> {
+    if info.pos() == "記号" {
+        return None;
+    }
+    match info.base_form() {
+        Some(base) if !base.is_empty() && base != "*" => Some(base.to_string()),
+        _ => Some(info.text.clone()),
+    }
+}
+
+fn is_katakana_char(c: char) -> bool {
+    matches!(c, 'ァ'..='ヺ' | 'ー' | 'ヽ' | 'ヾ')
+}
+
+// カタカナの外来語（コンピュータ、コーヒー牛乳）かどうか
+// 表記の半分を超える文字がカタカナの名詞を外来語とする。固有名詞と、
+// 同じ音の繰り返し（ガタガタ・キラキラ）は擬音語・擬態語とみなして除く
+fn is_katakana_loanword(info: &TokenInfo) -> bool {
+    if info.pos() != "名詞" || info.pos_detail_1() == Some("固有名詞") {
+        return false;
+    }
+
+    let chars: Vec<char> = info.text.chars().collect();
+    let katakana = chars.iter().filter(|c| is_katakana_char(**c)).count();
+    if katakana * 2 <= chars.len() {
+        return false;
+    }
+
+    let half = chars.len() / 2;
+    let reduplicated = chars.len().is_multiple_of(2) && half >= 2 && chars[..half] == chars[half..];
+    !reduplicated
+}
+
+// IPADIC では記号の読みに表記がそのまま入っているので、読みとして扱わない
+fn word_reading(info: &TokenInfo) -> Option<String> {
+    if info.pos() == "記号" {
+        return None;
+    }
+    match info.reading() {
+        Some(reading) if !reading.is_empty() && reading != "*" => {
+            Some(katakana_to_hiragana(reading))
+        }
+        _ => None,
+    }
+}
+
+// トークンごとの表記と品詞、読みを返す
+// 空のテキストは EmptyInput にする
+// 境界判定のオプションで正規化が指定されていれば、解析前に正規化する
+// pos_filter を指定した場合は、その品詞のトークンだけ読みなどを求めて返す
+pub fn analyze_text(
+    text: String,
+    pos_filter: Option<&[String]>,
+) -> Result<Vec<WordInfo>, BunsetsuError> {
+    let loaded = get_tokenizer().map_err(|e| BunsetsuError::TokenizerInit(e.to_string()))?;
+    analyze_with(&loaded, text, pos_filter, &active_boundary_rules())
+}
+
+fn analyze_with(
+    loaded: &LoadedTokenizer,
+    text: String,
+    pos_filter: Option<&[String]>,
+    rules: &BoundaryOptions,
+) -> Result<Vec<WordInfo>, BunsetsuError> {
+    if text.trim().is_empty() {
+        return Err(BunsetsuError::EmptyInput);
+    }
+    let text = match rules.normalize {
+        Some(normalize) => normalize_text(&text, normalize),
+        None => text,
+    };
+    let token_infos = tokenize_cached(loaded, &text)?;
+    Ok(match pos_filter {
+        Some(filter) => {
+            let selected: Vec<TokenInfo> = token_infos
+                .iter()
+                .filter(|info| filter.iter().any(|pos| pos == info.pos()))
+                .cloned()
+                .collect();
+            word_infos(&selected)
+        }
+        None => word_infos(&token_infos),
+    })
+}
+
+// 字幕の各行など、多数の短いテキストをまとめて文節に分ける
+// トークナイザと境界判定のオプションは一度だけ取得し、結果は入力と同じ順に返す
+// 一部のテキストが失敗しても残りは分ける（空のテキストは EmptyInput になる）
+pub fn split_text_into_bunsetsu_batch(
+    texts: Vec<String>,
+) -> Vec<Result<Vec<String>, BunsetsuError>> {
+    let loaded = match get_tokenizer() {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            let error = BunsetsuError::TokenizerInit(e.to_string());
+            return texts.iter().map(|_| Err(error.clone())).collect();
+        }
+    };
+    let options = active_boundary_rules();
+    texts
+        .into_iter()
+        .map(|text| {
+            if text.trim().is_empty() {
+                return Err(BunsetsuError::EmptyInput);
+            }
+            split_phrases_with(&loaded, text, &options)
+        })
+        .collect()
+}
+
+// 多数のテキストをまとめて形態素解析する（split_text_into_bunsetsu_batch の analyze_text 版）
+pub fn analyze_text_batch(
+    texts: Vec<String>,
+    pos_filter: Option<&[String]>,
+) -> Vec<Result<Vec<WordInfo>, BunsetsuError>> {
+    let loaded = match get_tokenizer() {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            let error = BunsetsuError::TokenizerInit(e.to_string());
+            return texts.iter().map(|_| Err(error.clone())).collect();
+        }
+    };
+    let rules = active_boundary_rules();
+    texts
+        .into_iter()
+        .map(|text| analyze_with(&loaded, text, pos_filter, &rules))
+        .collect()
+}
+
+// 形態素解析の結果と文節分割の結果を、一度の解析でまとめて返す
+#[derive(Debug, Serialize)]
+pub struct AnalyzeAndSplit {
+    words: Vec<WordInfo>,
+    phrases: Vec<String>,
+    // 各文節を構成するトークン（words の添字）の範囲
+    phrase_token_ranges: Vec<Range<usize>>,
+}
+
+pub fn analyze_and_split_text(text: String) -> Result<AnalyzeAndSplit, BunsetsuError> {
+    let (token_infos, phrase_token_ranges) = source_segments(&text)?;
+    let phrases = phrase_token_ranges
+        .iter()
+        .map(|range| join_token_texts(&token_infos[range.clone()]))
+        .collect();
+
+    Ok(AnalyzeAndSplit {
+        words: word_infos(&token_infos),
+        phrases,
+        phrase_token_ranges,
+    })
+}
+
+// 文節とその文節を終わらせた境界の確からしさ
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PhraseConfidence {
+    text: String,
+    confidence: f64,
+}
+
+// 文節分割に加えて、各文節の末尾の境界の確からしさを返す
+// テキスト末尾で終わる文節は 1.0
+pub fn split_text_with_confidence(text: String) -> Result<Vec<PhraseConfidence>, BunsetsuError> {
+    let (token_infos, ranges) = source_segments(&text)?;
+    let phrases = ranges
+        .into_iter()
+        .map(|range| {
+            let confidence = match token_infos.get(range.end) {
+                Some(next) => boundary_confidence(&token_infos[range.end - 1], next),
+                None => 1.0,
+            };
+            PhraseConfidence {
+                text: join_token_texts(&token_infos[range]),
+                confidence,
+            }
+        })
+        .collect();
+
+    Ok(phrases)
+}
+
+// 文節分割の結果（元のテキストと文節列）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SplitResult {
+    text: String,
+    phrases: Vec<String>,
+    // テキスト全体のトークン列（表記と品詞）のハッシュ（16 進数）。編集後に再描画が必要かどうかの判定用
+    // 文節ごとのハッシュ（phrase_hashes）をつなげて求めるので、結合時に全体を解析し直さずに済む
+    #[serde(default)]
+    token_hash: String,
+    // 文節ごとのトークン列のハッシュ（「16 進数のハッシュ:バイト数」、phrases と同じ順）
+    #[serde(default)]
+    phrase_hashes: Vec<String>,
+}
+
+// トークンの表記と品詞を順にハッシュした値と、ハッシュしたバイト数
+// 多項式ハッシュ（2^64 を法とする）なので、列 a と b をつなげた列のハッシュは H(a)・B^len(b) + H(b) になり、
+// 文節ごとのハッシュからトークン列全体のハッシュを求められる
+// 実行ごと・環境ごとに値が変わらないよう、標準ライブラリの DefaultHasher は使わない
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct StreamHash {
+    hash: u64,
+    len: u64,
+}
+
+const STREAM_HASH_BASE: u64 = 0x0000_0100_0000_01b3;
+
+impl StreamHash {
+    const EMPTY: StreamHash = StreamHash { hash: 0, len: 0 };
+
+    fn of_tokens(token_infos: &[TokenInfo]) -> StreamHash {
+        // 区切りのバイトを挟み、「ab」+「c」と「a」+「bc」を区別する
+        token_infos
+            .iter()
+            .flat_map(|info| {
+                info.text
+                    .bytes()
+                    .chain([0x1f])
+                    .chain(info.pos().bytes())
+                    .chain([0x1e])
+            })
+            .fold(StreamHash::EMPTY, |acc, byte| StreamHash {
+                hash: acc
+                    .hash
+                    .wrapping_mul(STREAM_HASH_BASE)
+                    .wrapping_add(u64::from(byte) + 1),
+                len: acc.len + 1,
+            })
+    }
+
+    // self の列の後ろに next の列をつなげた列のハッシュ
+    fn then(self, next: StreamHash) -> StreamHash {
+        let mut shift: u64 = 1;
+        let mut base = STREAM_HASH_BASE;
+        let mut exp = next.len;
+        while exp > 0 {
+            if exp & 1 == 1 {
+                shift = shift.wrapping_mul(base);
+            }
+            base = base.wrapping_mul(base);
+            exp >>= 1;
+        }
+        StreamHash {
+            hash: self.hash.wrapping_mul(shift).wrapping_add(next.hash),
+            len: self.len + next.len,
+        }
+    }
+
+    fn encode(self) -> String {
+        format!("{:016x}:{}", self.hash, self.len)
+    }
+
+    fn decode(encoded: &str) -> Option<StreamHash> {
+        let (hash, len) = encoded.split_once(':')?;
+        Some(StreamHash {
+            hash: u64::from_str_radix(hash, 16).ok()?,
+            len: len.parse().ok()?,
+        })
+    }
+}
+
+// 文節とそのトークン列のハッシュの組から結果を作る
+// 文節はトークン列を隙間なく覆うので、文節ごとのハッシュをつなげたものがテキスト全体のトークン列のハッシュになる
+fn new_split_result(text: String, hashed_phrases: Vec<(String, StreamHash)>) -> SplitResult {
+    let token_hash = hashed_phrases
+        .iter()
+        .fold(StreamHash::EMPTY, |acc, (_, hash)| acc.then(*hash));
+    let (phrases, phrase_hashes) = hashed_phrases
+        .into_iter()
+        .map(|(phrase, hash)| (phrase, hash.encode()))
+        .unzip();
+    SplitResult {
+        text,
+        phrases,
+        token_hash: format!("{:016x}", token_hash.hash),
+        phrase_hashes,
+    }
+}
+
+// 文節に分けると同時に、文節ごとのトークン列をハッシュする（テキストの解析は一度だけ）
+fn split_hashed_phrases(text: String) -> Result<Vec<(String, StreamHash)>, BunsetsuError> {
+    let loaded = get_tokenizer().map_err(|e| BunsetsuError::TokenizerInit(e.to_string()))?;
+    segment_text_with(&loaded, text, &active_boundary_rules(), |token_infos| {
+        (
+            join_token_texts(token_infos),
+            StreamHash::of_tokens(token_infos),
+        )
+    })
+}
+
+// 結果の文節とハッシュの組を返す
+// 文節ごとのハッシュを持たない結果（以前の形式）は、文節をつなげたテキスト全体を一度だけ解析し、
+// 各トークンをそれが始まる文節に割り当ててハッシュする
+fn hashed_phrases_of(result: SplitResult) -> Result<Vec<(String, StreamHash)>, BunsetsuError> {
+    let decoded: Option<Vec<StreamHash>> = result
+        .phrase_hashes
+        .iter()
+        .map(|hash| StreamHash::decode(hash))
+        .collect();
+    if let Some(hashes) = decoded.filter(|hashes| hashes.len() == result.phrases.len()) {
+        return Ok(result.phrases.into_iter().zip(hashes).collect());
+    }
+
+    let token_infos = tokenize_to_infos(&result.phrases.concat())?;
+    let mut tokens = token_infos.iter().peekable();
+    let mut phrase_end = 0;
+    let mut hashed_phrases = Vec::new();
+    for phrase in result.phrases {
+        phrase_end += phrase.len();
+        let mut phrase_tokens = Vec::new();
+        while let Some(info) = tokens.next_if(|info| info.byte_start < phrase_end) {
+            phrase_tokens.push(info.clone());
+        }
+        hashed_phrases.push((phrase, StreamHash::of_tokens(&phrase_tokens)));
+    }
+    Ok(hashed_phrases)
+}
+
+pub fn split_text_into_result(text: String) -> Result<SplitResult, BunsetsuError> {
+    let hashed_phrases = split_hashed_phrases(text.clone())?;
+    Ok(new_split_result(text, hashed_phrases))
+}
+
+// 文末記号（文の区切りの判定はすべてこれを使う）
+// 「…」は文の途中にも現れる（えっと…それは）ので、単独では文末としない
+fn is_sentence_terminator(c: char) -> bool {
+    matches!(c, '。' | '！' | '？' | '!' | '?')
+}
+
+// 文末記号か改行で終わっていれば、後ろに何を追記しても文節はつながらない
+fn ends_with_sentence_boundary(phrase: &str) -> bool {
+    phrase
+        .chars()
+        .last()
+        .is_some_and(|c| is_sentence_terminator(c) || c == '\n')
+}
+
+// 追記されたテキストの解析結果を既存の結果につなげる
+// 継ぎ目（prev の最後の文節と appended の最初の文節）だけを再解析する
+pub fn merge_results(
+    prev: SplitResult,
+    appended: SplitResult,
+) -> Result<SplitResult, BunsetsuError> {
+    let text = format!("{}{}", prev.text, appended.text);
+    let mut phrases = hashed_phrases_of(prev)?;
+    let appended = hashed_phrases_of(appended)?;
+
+    let seam = match (phrases.last(), appended.first()) {
+        (Some((last, _)), Some((first, _))) if !ends_with_sentence_boundary(last) => {
+            Some(format!("{}{}", last, first))
+        }
+        _ => None,
+    };
+    let Some(seam) = seam else {
+        phrases.extend(appended);
+        return Ok(new_split_result(text, phrases));
+    };
+
+    // 継ぎ目の 2 文節だけを解析し直し、ほかの文節はハッシュも含めてそのまま使う
+    phrases.pop();
+    phrases.extend(split_hashed_phrases(seam)?);
+    phrases.extend(appended.into_iter().skip(1));
+
+    Ok(new_split_result(text, phrases))
+}
+
+// 読み（カナ）のモーラ数を数える
+// 拗音の小書き文字（ャ・ュ・ョなど）は前の文字と合わせて1モーラ、
+// 促音「ッ」・撥音「ン」・長音「ー」はそれぞれ1モーラとして数える
+pub fn count_mora(reading: &str) -> usize {
+    reading
+        .chars()
+        .filter(|c| {
+            is_kana(*c)
+                && !matches!(
+                    c,
+                    'ゃ' | 'ゅ'
+                        | 'ょ'
+                        | 'ぁ'
+                        | 'ぃ'
+                        | 'ぅ'
+                        | 'ぇ'
+                        | 'ぉ'
+                        | 'ゎ'
+                        | 'ャ'
+                        | 'ュ'
+                        | 'ョ'
+                        | 'ァ'
+                        | 'ィ'
+                        | 'ゥ'
+                        | 'ェ'
+                        | 'ォ'
+                        | 'ヮ'
+                )
+        })
+        .count()
+}
+
+fn is_kana(c: char) -> bool {
+    matches!(c, 'ぁ'..='ゖ' | 'ァ'..='ヺ' | 'ー')
+}
+
+// テキスト全体のモーラ数を数える（5・7・5 の判定用）
+// 読みのないトークン（記号など）は表記がかなであればそのまま数える
+pub fn count_text_mora(text: String) -> Result<usize, BunsetsuError> {
+    let token_infos = tokenize_to_infos(&text)?;
+    let mora = token_infos.iter().map(token_mora).sum();
+
+    Ok(mora)
+}
+
+fn token_mora(info: &TokenInfo) -> usize {
+    match info.reading() {
+        Some(reading) if reading != "*" => count_mora(reading),
+        _ => count_mora(&info.text),
+    }
+}
+
+// HTML に埋め込めるように特殊文字をエスケープする
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// 内容語（名詞・動詞・形容詞）を辞書引き用の span で囲んだ HTML を返す
+// span の data-base 属性には原形を入れる。助詞や記号はそのまま出力する
+pub fn annotate_text_with_lookup_links(text: String) -> Result<String, BunsetsuError> {
+    let token_infos = tokenize_to_infos(&text)?;
+    let mut html = String::new();
+
+    for info in token_infos.iter() {
+        let surface = escape_html(&info.text);
+        if matches!(info.pos(), "名詞" | "動詞" | "形容詞") {
+            let base = match info.base_form() {
+                Some(base) if base != "*" => base,
+                _ => info.text.as_str(),
+            };
+            html.push_str(&format!(
+                "<span class=\"lookup\" data-base=\"{}\">{}</span>",
+                escape_html(base),
+                surface
+            ));
+        } else {
+            html.push_str(&surface);
+        }
+    }
+
+    Ok(html)
+}
+
+// デバッグ用の文節分割結果（境界判定の経過つき）
+#[derive(Debug, Serialize)]
+pub struct DebugSplit {
+    phrases: Vec<String>,
+    trace: Vec<serde_json::Value>,
+}
+
+// 文節分割とあわせて、各トークン間の境界判定の経過を返す
+// verbose が false の場合 trace は空になる
+pub fn split_text_with_trace(text: String, verbose: bool) -> Result<DebugSplit, BunsetsuError> {
+    let options = source_options(&active_boundary_rules());
+    let (token_infos, ranges) = source_segments(&text)?;
+    let phrases = ranges
+        .into_iter()
+        .map(|range| join_token_texts(&token_infos[range]))
+        .collect();
+
+    let mut trace = Vec::new();
+    if verbose {
+        // 各トークンの文の中での位置（文が変わるたびに 0 に戻る）
+        let mut sentence_token_indices = vec![0; token_infos.len()];
+        for sentence in split_token_infos_into_sentences(&token_infos) {
+            let start = sentence.start;
+            for i in sentence {
+                sentence_token_indices[i] = i - start;
+            }
+        }
+
+        for (i, pair) in token_infos.windows(2).enumerate() {
+            let prev = if i > 0 { token_infos.get(i - 1) } else { None };
+            let (current, next) = (&pair[0], &pair[1]);
+            trace.push(serde_json::json!({
+                "index": i,
+                "sentence_token_index": sentence_token_indices[i],
+                "current": current.text,
+                "current_pos": current.pos(),
+                "current_pos_detail": current.pos_detail_1(),
+                "next": next.text,
+                "next_pos": next.pos(),
+                "boundary": is_bunsetsu_boundary(prev, current, next, &options),
+            }));
+        }
+    }
+
+    Ok(DebugSplit { phrases, trace })
+}
+
+// SSML 出力の間（ミリ秒）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SsmlOptions {
+    // 文節の境界に入れる間
+    pub phrase_break_ms: u32,
+    // 文の境界（。！？の後）に入れる間
+    pub sentence_break_ms: u32,
+}
+
+impl Default for SsmlOptions {
+    fn default() -> Self {
+        SsmlOptions {
+            phrase_break_ms: 100,
+            sentence_break_ms: 400,
+        }
+    }
+}
+
+fn contains_kanji(text: &str) -> bool {
+    text.chars().any(is_kanji_char)
+}
+
+// 英字の略語（NHK・DVD など）の読み。load_acronym_readings で読み込むまでは空
+static ACRONYM_READINGS: RwLock<BTreeMap<String, String>> = RwLock::new(BTreeMap::new());
+
+// よく使われる略語の読み
+pub fn default_acronym_readings() -> BTreeMap<String, String> {
+    [
+        ("NHK", "エヌエイチケー"),
+        ("DVD", "ディーブイディー"),
+        ("CD", "シーディー"),
+        ("PC", "ピーシー"),
+        ("USB", "ユーエスビー"),
+        ("ATM", "エーティーエム"),
+        ("AI", "エーアイ"),
+        ("IT", "アイティー"),
+    ]
+    .into_iter()
+    .map(|(acronym, reading)| (acronym.to_string(), reading.to_string()))
+    .collect()
+}
+
+// 略語の読みの表を差し替える（空の表を渡すと略語に読みを付けなくなる）
+pub fn load_acronym_readings(table: BTreeMap<String, String>) -> Result<(), BunsetsuError> {
+    *ACRONYM_READINGS
+        .write()
+        .map_err(|e| BunsetsuError::Internal(e.to_string()))? = table;
+    Ok(())
+}
+
+fn is_latin_word(text: &str) -> bool {
+    !text.is_empty()
+        && text
+            .chars()
+            .all(|c| c.is_ascii_alphabetic() || matches!(c, 'Ａ'..='Ｚ' | 'ａ'..='ｚ'))
+}
+
+// 英字だけの語の読みを略語の表から引く。表にない場合や表が読み込めない場合は None
+fn acronym_reading(text: &str) -> Option<String> {
+    if !is_latin_word(text) {
+        return None;
+    }
+    ACRONYM_READINGS.read().ok()?.get(text).cloned()
+}
+
+// 振り仮名・読み上げ用の読み（カタカナ）
+// 漢字を含む語は辞書の読み、英字の略語は略語の表の読みを使う
+fn annotation_reading(info: &TokenInfo) -> Option<String> {
+    if contains_kanji(&info.text) {
+        info.reading()
+            .filter(|reading| *reading != "*")
+            .map(str::to_string)
+    } else {
+        acronym_reading(&info.text)
+    }
+}
+
+// 助数詞の読みの頭の音の行（数字の後ろで促音・濁音・半濁音になるかが行で決まる）
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CounterRow {
+    K,
+    S,
+    T,
+    H,
+}
+
+// 前の数字によって読みが変わる助数詞と、その基本の読み
+const COUNTERS: &[(&str, &str, CounterRow)] = &[
+    ("本", "ホン", CounterRow::H),
+    ("杯", "ハイ", CounterRow::H),
+    ("匹", "ヒキ", CounterRow::H),
+    ("個", "コ", CounterRow::K),
+    ("回", "カイ", CounterRow::K),
+    ("冊", "サツ", CounterRow::S),
+    ("頭", "トウ", CounterRow::T),
+];
+
+const DIGIT_READINGS: [&str; 10] = [
+    "",
+    "イチ",
+    "ニ",
+    "サン",
+    "ヨン",
+    "ゴ",
+    "ロク",
+    "ナナ",
+    "ハチ",
+    "キュウ",
+];
+
+// 各位の読み（place は 0 が一の位、1 が十の位、2 が百の位、3 が千の位）
+fn place_reading(digit: usize, place: usize) -> String {
+    match (place, digit) {
+        (1, 1) => "ジュウ".to_string(),
+        (1, _) => format!("{}ジュウ", DIGIT_READINGS[digit]),
+        (2, 1) => "ヒャク".to_string(),
+        (2, 3) => "サンビャク".to_string(),
+        (2, 6) => "ロッピャク".to_string(),
+        (2, 8) => "ハッピャク".to_string(),
+        (2, _) => format!("{}ヒャク", DIGIT_READINGS[digit]),
+        (3, 1) => "セン".to_string(),
+        (3, 3) => "サンゼン".to_string(),
+        (3, 8) => "ハッセン".to_string(),
+        (3, _) => format!("{}セン", DIGIT_READINGS[digit]),
+        _ => DIGIT_READINGS[digit].to_string(),
+    }
+}
+
+// 助数詞の前で促音になる数の読みの末尾と、促音にした形
+// 3 つ目は K・H 行以外（サ行・タ行）の助数詞の前でも促音になるかどうか（ロクサツ・ロクトウは促音にならない）
+const GEMINATION: [(&str, &str, bool); 5] = [
+    ("イチ", "イッ", true),
+    ("ハチ", "ハッ", true),
+    ("ジュウ", "ジュッ", true),
+    ("ロク", "ロッ", false),
+    ("ャク", "ャッ", false),
+];
+
+// 算用数字（半角・全角）を 1〜9999 の数にする
+fn parse_arabic_number(text: &str) -> Option<usize> {
+    let mut number: usize = 0;
+    for c in text.chars() {
+        let digit = match c {
+            '0'..='9' => c as usize - '0' as usize,
+            '０'..='９' => c as usize - '０' as usize,
+            _ => return None,
+        };
+        number = number.checked_mul(10)?.checked_add(digit)?;
+    }
+    (1..=9999).contains(&number).then_some(number)
+}
+
+// ハ行の頭の音を濁音・半濁音にする（ホン → ボン・ポン）
+fn h_row_variant(reading: &str, semi_voiced: bool) -> String {
+    let mut chars = reading.chars();
+    let first = chars.next().map(|c| match (c, semi_voiced) {
+        ('ハ', false) => 'バ',
+        ('ハ', true) => 'パ',
+        ('ヒ', false) => 'ビ',
+        ('ヒ', true) => 'ピ',
+        ('フ', false) => 'ブ',
+        ('フ', true) => 'プ',
+        ('ヘ', false) => 'ベ',
+        ('ヘ', true) => 'ペ',
+        ('ホ', false) => 'ボ',
+        ('ホ', true) => 'ポ',
+        (c, _) => c,
+    });
+    first.into_iter().chain(chars).collect()
+}
+
+// 数字と助数詞を合わせた読み（カタカナ）。音便を反映する（1本 → イッポン、3本 → サンボン、6本 → ロッポン）
+// 表にない助数詞や 1〜9999 以外の数は None
+pub fn counter_reading(number: &str, counter: &str) -> Option<String> {
+    let &(_, counter_kana, row) = COUNTERS.iter().find(|(text, _, _)| *text == counter)?;
+    let number = parse_arabic_number(number)?;
+
+    let mut parts: Vec<String> = Vec::new();
+    for place in (0..4).rev() {
+        let digit = number / 10usize.pow(place as u32) % 10;
+        if digit != 0 {
+            parts.push(place_reading(digit, place));
+        }
+    }
+    // 音便は最後の位の読みにだけ起こる
+    let mut last = parts.pop()?;
+
+    // 促音便（イチ → イッ、ハチ → ハッ、ジュウ → ジュッ、ロク → ロッ、ヒャク → ヒャッ）
+    let gemination = GEMINATION
+        .iter()
+        .find(|(plain, _, _)| last.ends_with(plain))
+        .filter(|(_, _, all_rows)| *all_rows || matches!(row, CounterRow::K | CounterRow::H));
+    let geminated = match gemination {
+        Some((plain, short, _)) => {
+            last.truncate(last.len() - plain.len());
+            last.push_str(short);
+            true
+        }
+        None => false,
+    };
+
+    let counter_kana = match row {
+        CounterRow::H if geminated => h_row_variant(counter_kana, true),
+        // 3 と千の後ろのハ行は濁音になる（サンボン、センボン）
+        CounterRow::H
+            if last.ends_with("サン") || last.ends_with("セン") || last.ends_with("ゼン") =>
+        {
+            h_row_variant(counter_kana, false)
+        }
+        _ => counter_kana.to_string(),
+    };
+
+    Some(format!("{}{}{}", parts.concat(), last, counter_kana))
+}
+
+// 読み上げ用の SSML を生成する
+// 文節の境界と文の境界に <break> を入れ、漢字を含む語には読みを <sub> で付ける
+pub fn text_to_ssml(text: String, options: &SsmlOptions) -> Result<String, BunsetsuError> {
+    let (token_infos, ranges) = source_segments(&text)?;
+
+    let mut ssml = String::from("<speak>");
+    for (i, range) in ranges.iter().enumerate() {
+        let phrase = &token_infos[range.clone()];
+
+        if i > 0 {
+            let prev = &token_infos[ranges[i - 1].clone()];
+            let prev_text = join_token_texts(prev);
+            if ends_with_sentence_boundary(&prev_text) {
+                ssml.push_str(&format!(
+                    "<break time=\"{}ms\"/>",
+                    options.sentence_break_ms
+                ));
+            } else if !phrase.iter().all(|info| info.pos() == "記号") {
+                // 句読点だけの文節の前には間を入れない
+                ssml.push_str(&format!("<break time=\"{}ms\"/>", options.phrase_break_ms));
+            }
+        }
+
+        let mut j = 0;
+        while j < phrase.len() {
+            let info = &phrase[j];
+            // 数字と助数詞は音便を反映した読みを合わせて付ける（3本 → サンボン）
+            if let Some(counter) = phrase.get(j + 1) {
+                if let Some(reading) = counter_reading(&info.text, &counter.text) {
+                    ssml.push_str(&format!(
+                        "<sub alias=\"{}\">{}{}</sub>",
+                        escape_html(&reading),
+                        escape_html(&info.text),
+                        escape_html(&counter.text)
+                    ));
+                    j += 2;
+                    continue;
+                }
+            }
+
+            match annotation_reading(info) {
+                Some(reading) => {
+                    ssml.push_str(&format!(
+                        "<sub alias=\"{}\">{}</sub>",
+                        escape_html(&reading),
+                        escape_html(&info.text)
+                    ));
+                }
+                _ => ssml.push_str(&escape_html(&info.text)),
+            }
+            j += 1;
+        }
+    }
+    ssml.push_str("</speak>");
+
+    Ok(ssml)
+}
+
+// 隣り合うトークン間の境界判定結果をそのまま返す（規則変更の回帰テスト用）
+// 設定で変えた規則に左右されないよう、常に既定の規則で判定する
+pub fn boundary_vector(text: String) -> Result<Vec<bool>, BunsetsuError> {
+    let token_infos = tokenize_to_infos(&text)?;
+    Ok(compute_boundaries(
+        &token_infos,
+        &BoundaryOptions::default(),
+    ))
+}
+
+// 同じ読みを持つ異なる表記をまとめる（同音異義語の学習用）
+// 読み → 表記の一覧。表記が2種類以上ある読みだけを返す
+pub fn homophone_groups_in_text(
+    text: String,
+) -> Result<BTreeMap<String, Vec<String>>, BunsetsuError> {
+    let token_infos = tokenize_to_infos(&text)?;
+    let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    for info in token_infos.iter() {
+        // 助詞・助動詞・記号は対象外
+        if matches!(info.pos(), "助詞" | "助動詞" | "記号") {
+            continue;
+        }
+        let Some(reading) = info.reading().filter(|r| *r != "*") else {
+            continue;
+        };
+        let surfaces = groups.entry(reading.to_string()).or_default();
+        if !surfaces.contains(&info.text) {
+            surfaces.push(info.text.clone());
+        }
+    }
+
+    groups.retain(|_, surfaces| surfaces.len() > 1);
+    Ok(groups)
+}
+
+// 口語の縮約形を元の形に展開する
+// てる→ている、でる→でいる、ちゃう→てしまう、じゃう→でしまう、なきゃ→なければ、わかんない→わからない
+// 「捨てる」などを壊さないよう、文字列置換ではなくトークンの品詞・原形を見て展開する
+pub fn expand_contractions(text: &str) -> Result<String, BunsetsuError> {
+    let token_infos = tokenize_to_infos(text)?;
+    let mut expanded = String::with_capacity(text.len());
+
+    for info in token_infos.iter() {
+        let surface = info.text.as_str();
+        let is_auxiliary_verb = info.pos() == "動詞" && info.pos_detail_1() == Some("非自立");
+
+        let replaced = match info.base_form() {
+            // 〜てる / 〜でる（てる・てた・てない → ている・ていた・ていない）
+            Some("てる") if is_auxiliary_verb => {
+                surface.strip_prefix('て').map(|s| format!("てい{}", s))
+            }
+            Some("でる") if is_auxiliary_verb => {
+                surface.strip_prefix('で').map(|s| format!("でい{}", s))
+            }
+            // 〜ちゃう / 〜じゃう（ちゃう・ちゃった → てしまう・てしまった）
+            Some("ちゃう") if is_auxiliary_verb => {
+                surface.strip_prefix("ちゃ").map(|s| format!("てしま{}", s))
+            }
+            Some("じゃう") if is_auxiliary_verb => {
+                surface.strip_prefix("じゃ").map(|s| format!("でしま{}", s))
+            }
+            // 〜なきゃ
+            Some("ない") if surface == "なきゃ" => Some("なければ".to_string()),
+            _ => None,
+        };
+
+        // ラ行五段の撥音化（わかんない → わからない）
+        let replaced = replaced.or_else(|| {
+            if info.conjugation_form() == Some("未然特殊")
+                && info.conjugation_type() == Some("五段・ラ行")
+            {
+                surface.strip_suffix('ん').map(|s| format!("{}ら", s))
+            } else {
+                None
+            }
+        });
+
+        expanded.push_str(replaced.as_deref().unwrap_or(surface));
+    }
+
+    Ok(expanded)
+}
+
+fn is_kanji_char(c: char) -> bool {
+    // 拡張 B 以降（𠮷 など）はサロゲートペアで表される文字
+    matches!(c, '一'..='鿿' | '㐀'..='䶿' | '\u{20000}'..='\u{3134F}' | '々' | '〆' | 'ヶ')
+}
+
+// カタカナをひらがなに変換する（長音記号などはそのまま）
+fn katakana_to_hiragana(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            'ァ'..='ヶ' => char::from_u32(c as u32 - 0x60).unwrap_or(c),
+            _ => c,
+        })
+        .collect()
+}
+
+// 解析前の文字の正規化（PDF などから貼り付けた全角英数字や半角カタカナを揃える）
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NormalizeOptions {
+    // 全角の英数字・記号と全角の空白を半角にする（ＡＢＣ → ABC、１２３ → 123）
+    pub fold_width: bool,
+    // 半角カタカナを全角にし、濁点・半濁点を合成する（ｶﾞ → ガ）
+    pub halfwidth_katakana: bool,
+    // 全角チルダ「～」を波ダッシュ「〜」にそろえる
+    pub unify_wave_dash: bool,
+}
+
+impl Default for NormalizeOptions {
+    fn default() -> Self {
+        NormalizeOptions {
+            fold_width: true,
+            halfwidth_katakana: true,
+            unify_wave_dash: true,
+        }
+    }
+}
+
+// 半角カタカナ（U+FF61〜U+FF9D）に対応する全角の文字
+const HALFWIDTH_KATAKANA: &str = "。「」、・ヲァィゥェォャュョッーアイウエオカキクケコサシスセソタチツテトナニヌネノハヒフヘホマミムメモヤユヨラリルレロワン";
+
+// 濁点・半濁点を前のカタカナと合成する（カ + ゛ → ガ、ハ + ゜ → パ、ウ + ゛ → ヴ）
+fn compose_sound_mark(base: char, semi_voiced: bool) -> Option<char> {
+    let code = base as u32;
+    match (base, semi_voiced) {
+        ('ウ', false) => Some('ヴ'),
+        ('カ'..='チ', false) if (code - 'カ' as u32).is_multiple_of(2) => {
+            char::from_u32(code + 1)
+        }
+        // 「ッ」が間に入るので、ツ・テ・ト は上の偶奇から外れる
+        ('ツ' | 'テ' | 'ト', false) => char::from_u32(code + 1),
+        ('ハ'..='ホ', _) if (code - 'ハ' as u32).is_multiple_of(3) => {
+            char::from_u32(code + if semi_voiced { 2 } else { 1 })
+        }
+        _ => None,
+    }
+}
+
+// 文字の幅などを正規化する。元の文字をそのまま残したい場合は呼ばない
+pub fn normalize_text(text: &str, options: NormalizeOptions) -> String {
+    let mut normalized = String::with_capacity(text.len());
+    for c in text.chars() {
+        let c = match c {
+            '～' if options.unify_wave_dash => '〜',
+            '！'..='～' if options.fold_width => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+            '\u{3000}' if options.fold_width => ' ',
+            '\u{FF61}'..='\u{FF9D}' if options.halfwidth_katakana => HALFWIDTH_KATAKANA
+                .chars()
+                .nth((c as u32 - 0xFF61) as usize)
+                .unwrap_or(c),
+            '\u{FF9E}' | '\u{FF9F}' if options.halfwidth_katakana => {
+                let semi_voiced = c == '\u{FF9F}';
+                match normalized
+                    .chars()
+                    .last()
+                    .and_then(|base| compose_sound_mark(base, semi_voiced))
+                {
+                    Some(composed) => {
+                        normalized.pop();
+                        composed
+                    }
+                    None if semi_voiced => '゜',
+                    None => '゛',
+                }
+            }
+            _ => c,
+        };
+        normalized.push(c);
+    }
+    normalized
+}
+
+// 表記と読み（ひらがな）を送り仮名で揃え、漢字の連続部分にだけ読みを割り当てる
+// 例: 食べ / たべ → [("食", Some("た")), ("べ", None)]
+// 揃えられない場合は None を返す
+fn align_okurigana(surface: &str, reading: &str) -> Option<Vec<(String, Option<String>)>> {
+    let mut segments: Vec<(String, bool)> = Vec::new();
+    for c in surface.chars() {
+        let kanji = is_kanji_char(c);
+        match segments.last_mut() {
+            Some((text, is_kanji)) if *is_kanji == kanji => text.push(c),
+            _ => segments.push((c.to_string(), kanji)),
+        }
+    }
+
+    let reading: Vec<char> = reading.chars().collect();
+    align_segments(&segments, &reading)
+}
+
+fn align_segments(
+    segments: &[(String, bool)],
+    reading: &[char],
+) -> Option<Vec<(String, Option<String>)>> {
+    let Some(((text, is_kanji), rest)) = segments.split_first() else {
+        return reading.is_empty().then(Vec::new);
+    };
+
+    if !*is_kanji {
+        // 仮名の部分は読みと一致しなければならない
+        let kana: Vec<char> = katakana_to_hiragana(text).chars().collect();
+        if !reading.starts_with(&kana) {
+            return None;
+        }
+        let mut aligned = align_segments(rest, &reading[kana.len()..])?;
+        aligned.insert(0, (text.clone(), None));
+        return Some(aligned);
+    }
+
+    // 漢字の部分には 1 文字以上の読みを割り当て、残りが揃うものを探す
+    for len in 1..=reading.len() {
+        if let Some(mut aligned) = align_segments(rest, &reading[len..]) {
+            aligned.insert(0, (text.clone(), Some(reading[..len].iter().collect())));
+            return Some(aligned);
+        }
+    }
+    None
+}
+
+// 青空文庫形式のルビ（｜漢字《かんじ》）を付けたテキストを返す
+// 送り仮名には読みを付けず、漢字の連続部分ごとに ｜ と《》を置く
+pub fn to_aozora_ruby(text: String) -> Result<String, BunsetsuError> {
+    let token_infos = tokenize_to_infos(&text)?;
+
+    let mut ruby = String::with_capacity(text.len() * 2);
+    for info in token_infos.iter() {
+        let reading = match info.reading() {
+            Some(reading) if reading != "*" && contains_kanji(&info.text) => {
+                katakana_to_hiragana(reading)
+            }
+            _ => {
+                ruby.push_str(&info.text);
+                continue;
+            }
+        };
+
+        match align_okurigana(&info.text, &reading) {
+            Some(aligned) => {
+                for (segment, segment_reading) in aligned {
+                    match segment_reading {
+                        Some(r) => ruby.push_str(&format!("｜{}《{}》", segment, r)),
+                        None => ruby.push_str(&segment),
+                    }
+                }
+            }
+            // 揃えられない語（熟字訓など）は語全体にルビを付ける
+            None => ruby.push_str(&format!("｜{}《{}》", info.text, reading)),
+        }
+    }
+
+    Ok(ruby)
+}
+
+// 振り仮名を付ける単位。漢字を含む部分にだけ読み（ひらがな）がある
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RubySegment {
+    pub surface: String,
+    pub reading: Option<String>,
+}
+
+// トークンを送り仮名で揃えたルビの単位に分けて返す（読ん|で → 読《よ》・んで）
+pub fn ruby_segments_in_text(text: String) -> Result<Vec<RubySegment>, BunsetsuError> {
+    let token_infos = tokenize_to_infos(&text)?;
+
+    let mut segments = Vec::new();
+    for info in token_infos.iter() {
+        let reading = match info.reading() {
+            Some(reading) if reading != "*" && contains_kanji(&info.text) => {
+                katakana_to_hiragana(reading)
+            }
+            _ => {
+                segments.push(RubySegment {
+                    surface: info.text.clone(),
+                    reading: None,
+                });
+                continue;
+            }
+        };
+
+        match align_okurigana(&info.text, &reading) {
+            Some(aligned) => {
+                segments.extend(
+                    aligned
+                        .into_iter()
+                        .map(|(surface, reading)| RubySegment { surface, reading }),
+                );
+            }
+            // 揃えられない語（熟字訓など）は語全体に読みを付ける
+            None => segments.push(RubySegment {
+                surface: info.text.clone(),
+                reading: Some(reading),
+            }),
+        }
+    }
+
+    Ok(segments)
+}
+
+// min_grade 年生以降に習う漢字（常用漢字・常用外を含む）を含むルビの単位にだけ <ruby> を付けた HTML を返す
+// やさしい漢字とかなはそのまま（HTML の特殊文字はエスケープする）
+pub fn to_ruby_html(text: String, min_grade: u8) -> Result<String, BunsetsuError> {
+    let mut html = String::with_capacity(text.len() * 2);
+    for segment in ruby_segments_in_text(text)? {
+        let difficult = needs_reading_for_grade(&segment.surface, min_grade);
+        match segment.reading.filter(|_| difficult) {
+            Some(reading) => html.push_str(&format!(
+                "<ruby>{}<rt>{}</rt></ruby>",
+                escape_html(&segment.surface),
+                escape_html(&reading)
+            )),
+            None => html.push_str(&escape_html(&segment.surface)),
+        }
+    }
+    Ok(html)
+}
+
+// 親文字 1 字あたりのルビの文字数の既定の上限（ルビは親文字の半分の大きさなので 2 字で親文字の幅になる）
+pub const DEFAULT_MAX_RUBY_PER_BASE: usize = 2;
+
+// ルビの単位と、表示幅の計算に使う親文字・ルビの文字数
+// ルビが上限を超える場合は overflows を立て、表示側でルビの文字を小さくできるようにする
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RubyAnnotation {
+    pub surface: String,
+    pub reading: Option<String>,
+    pub base_chars: usize,
+    pub ruby_chars: usize,
+    pub overflows: bool,
+}
+
+// ルビの単位ごとに文字数を数え、親文字 1 字あたり max_ruby_per_base 字を超えるルビに印を付ける
+pub fn ruby_annotations_in_text(
+    text: String,
+    max_ruby_per_base: usize,
+) -> Result<Vec<RubyAnnotation>, BunsetsuError> {
+    Ok(ruby_segments_in_text(text)?
+        .into_iter()
+        .map(|segment| {
+            let base_chars = segment.surface.chars().count();
+            let ruby_chars = segment
+                .reading
+                .as_deref()
+                .map_or(0, |reading| reading.chars().count());
+            RubyAnnotation {
+                surface: segment.surface,
+                reading: segment.reading,
+                base_chars,
+                ruby_chars,
+                overflows: ruby_chars > base_chars * max_ruby_per_base,
+            }
+        })
+        .collect())
+}
+
+// テキストをローマ字にする（語ごとに空白で区切る）
+// 読みのないトークン（英字・未知語など）は表記をそのまま使う
+pub fn romaji_of_text(text: String, style: RomajiStyle) -> Result<String, BunsetsuError> {
+    let token_infos = tokenize_to_infos(&text)?;
+
+    let mut romaji = String::with_capacity(text.len() * 2);
+    for info in token_infos.iter() {
+        if info.text.trim().is_empty() {
+            continue;
+        }
+        // 記号の前には空白を入れない
+        if !romaji.is_empty() && info.pos() != "記号" {
+            romaji.push(' ');
+        }
+        romaji.push_str(&token_romaji(info, style));
+    }
+
+    Ok(romaji)
+}
+
+fn token_romaji(info: &TokenInfo, style: RomajiStyle) -> String {
+    // 助詞の「は」「へ」「を」は発音どおりにする
+    if info.pos() == "助詞" {
+        match info.text.as_str() {
+            "は" => return "wa".to_string(),
+            "へ" => return "e".to_string(),
+            "を" => return "o".to_string(),
+            _ => {}
+        }
+    }
+
+    let reading = match info.reading() {
+        Some(reading) if !reading.is_empty() && reading != "*" => katakana_to_hiragana(reading),
+        _ => return info.text.clone(),
+    };
+
+    // 動詞の語尾の「う」（思う、言う）は長音にしない
+    if info.pos() == "動詞" {
+        if let Some(stem) = reading.strip_suffix('う') {
+            return format!("{}u", kana_to_romaji(stem, style));
+        }
+    }
+    kana_to_romaji(&reading, style)
+}
+
+// 文ごとの構造解析結果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SentenceStructure {
+    pub text: String,
+    // 述語（用言・助動詞）を含むかどうか。false なら体言止めか文の断片
+    pub has_predicate: bool,
+}
+
+// 文末が述語（用言・助動詞）で終わっているかどうか
+// 連体修飾の形容詞（青い空）は述語とみなさないよう、記号と終助詞を除いた最後の語で判定する
+fn has_predicate(sentence: &[TokenInfo]) -> bool {
+    last_word_before_final_particles(sentence)
+        .is_some_and(|info| matches!(info.pos(), "動詞" | "形容詞" | "助動詞"))
+}
+
+// 末尾の記号と終助詞を除いた最後の語
+fn last_word_before_final_particles(tokens: &[TokenInfo]) -> Option<&TokenInfo> {
+    tokens.iter().rev().find(|info| {
+        info.pos() != "記号" && !(info.pos() == "助詞" && info.pos_detail_1() == Some("終助詞"))
+    })
+}
+
+// トークン列を文末記号で文ごとに区切る
+fn split_token_infos_into_sentences(token_infos: &[TokenInfo]) -> Vec<Range<usize>> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    for (i, info) in token_infos.iter().enumerate() {
+        if ends_with_sentence_boundary(&info.text) {
+            sentences.push(start..i + 1);
+            start = i + 1;
+        }
+    }
+    if start < token_infos.len() {
+        sentences.push(start..token_infos.len());
+    }
+    sentences
+}
+
+// 文ごとに述語の有無を判定する
+pub fn analyze_sentence_structure(text: String) -> Result<Vec<SentenceStructure>, BunsetsuError> {
+    let token_infos = tokenize_to_infos(&text)?;
+
+    Ok(split_token_infos_into_sentences(&token_infos)
+        .into_iter()
+        .map(|range| {
+            let sentence = &token_infos[range];
+            SentenceStructure {
+                text: join_token_texts(sentence),
+                has_predicate: has_predicate(sentence),
+            }
+        })
+        .filter(|sentence| !sentence.text.trim().is_empty())
+        .collect())
+}
+
+// 語の出現頻度の集計オプション
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FrequencyOptions {
+    // 表記ゆれ（送り仮名・かな/漢字）をまとめてから数える
+    pub normalize_variants: bool,
+    // 英字の語を小文字にそろえてから数える（API・api・Api をまとめる）
+    pub fold_latin_case: bool,
+}
+
+// 表記ゆれ → 代表表記
+// 形態素解析で複数のトークンに分かれる表記（子／ども など）もあるので、解析後のトークンの並びと照合する
+const SPELLING_VARIANTS: &[(&str, &str)] = &[
+    ("子ども", "子供"),
+    ("こども", "子供"),
+    ("引越し", "引っ越し"),
+    ("受付け", "受け付け"),
+    ("申込み", "申し込み"),
+    ("取扱い", "取り扱い"),
+    ("話合い", "話し合い"),
+];
+
+// i 番目のトークンから始まる表記ゆれ（代表表記そのものも含む）を探し、代表表記とトークン数を返す
+// 一つのトークンなら原形で、複数のトークンに分かれていれば表記をつなげて照合する
+// トークンの境界にそろった並びだけを照合するので、語をまたぐ部分（ねこ＋ども）には当たらない
+fn spelling_variant_at(token_infos: &[TokenInfo], i: usize) -> Option<(&'static str, usize)> {
+    let first = token_infos.get(i)?;
+    SPELLING_VARIANTS.iter().find_map(|&(variant, canonical)| {
+        [variant, canonical].into_iter().find_map(|spelling| {
+            if first.base_form() == Some(spelling) {
+                return Some((canonical, 1));
+            }
+            let mut surface = String::new();
+            for (n, info) in token_infos[i..].iter().enumerate() {
+                surface.push_str(&info.text);
+                if surface == spelling {
+                    return Some((canonical, n + 1));
+                }
+                if !spelling.starts_with(surface.as_str()) {
+                    break;
+                }
+            }
+            None
+        })
+    })
+}
+
+// 語ごとの出現回数を数える（助詞・助動詞・記号は対象外）
+// normalize_variants が有効な場合は表記ゆれをまとめ、原形で数える
+pub fn word_frequencies_in_text(
+    text: String,
+    options: &FrequencyOptions,
+) -> Result<BTreeMap<String, usize>, BunsetsuError> {
+    let token_infos = tokenize_to_infos(&text)?;
+    let mut frequencies: BTreeMap<String, usize> = BTreeMap::new();
+
+    let mut i = 0;
+    while i < token_infos.len() {
+        if options.normalize_variants {
+            if let Some((canonical, len)) = spelling_variant_at(&token_infos, i) {
+                *frequencies.entry(canonical.to_string()).or_insert(0) += 1;
+                i += len;
+                continue;
+            }
+        }
+        let info = &token_infos[i];
+        i += 1;
+        if matches!(info.pos(), "助詞" | "助動詞" | "記号") {
+            continue;
+        }
+        let word = match info.base_form() {
+            Some(base) if options.normalize_variants && base != "*" => base,
+            _ => info.text.as_str(),
+        };
+        let word = if options.fold_latin_case && word.chars().all(is_latin_or_digit) {
+            word.to_lowercase()
+        } else {
+            word.to_string()
+        };
+        *frequencies.entry(word).or_insert(0) += 1;
+    }
+
+    Ok(frequencies)
+}
+
+// 語彙リストの作成オプション
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VocabOptions {
+    // 助詞・助動詞・記号も語彙に含める
+    pub include_function_words: bool,
+}
+
+// 語彙リストの一語（活用した形は辞書形にまとめる）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VocabEntry {
+    pub base_form: String,
+    // 辞書形の読み（ひらがな）。求められない場合は None
+    pub reading: Option<String>,
+    pub pos: String,
+    pub count: usize,
+}
+
+// 活用した語の読みから辞書形の読みを求める
+// 表記と辞書形の共通部分より後ろ（走っ → 走る の「っ」「る」）を読みの末尾で置き換える
+fn base_form_reading(info: &TokenInfo, base: &str) -> Option<String> {
+    let reading = word_reading(info)?;
+    if base == info.text {
+        return Some(reading);
+    }
+
+    let common = info
+        .text
+        .chars()
+        .zip(base.chars())
+        .take_while(|(a, b)| a == b)
+        .map(|(c, _)| c.len_utf8())
+        .sum::<usize>();
+    let surface_rest = katakana_to_hiragana(&info.text[common..]);
+    let base_rest = katakana_to_hiragana(&base[common..]);
+    let stem = reading.strip_suffix(surface_rest.as_str())?;
+    Some(format!("{}{}", stem, base_rest))
+}
+
+// テキストの語彙リストを作る（食べた・食べる・食べて は 食べる 一語として数える）
+// 出現回数の多い順、同じ回数なら読みの順に並べる
+pub fn vocabulary_in_text(
+    text: String,
+    options: &VocabOptions,
+) -> Result<Vec<VocabEntry>, BunsetsuError> {
+    let token_infos = tokenize_to_infos(&text)?;
+    Ok(vocabulary_of_tokens(&token_infos, options)
+        .into_iter()
+        .map(|(entry, _)| entry)
+        .collect())
+}
+
+// 語彙リストの各語と、その語が最初に出てくるトークンの添字
+fn vocabulary_of_tokens(
+    token_infos: &[TokenInfo],
+    options: &VocabOptions,
+) -> Vec<(VocabEntry, usize)> {
+    let mut entries: Vec<(VocabEntry, usize)> = Vec::new();
+
+    for (i, info) in token_infos.iter().enumerate() {
+        if !options.include_function_words && matches!(info.pos(), "助詞" | "助動詞" | "記号")
+        {
+            continue;
+        }
+        if info.text.trim().is_empty() {
+            continue;
+        }
+        let base = word_base_form(info).unwrap_or_else(|| info.text.clone());
+        match entries
+            .iter_mut()
+            .find(|(entry, _)| entry.base_form == base && entry.pos == info.pos())
+        {
+            Some((entry, _)) => entry.count += 1,
+            None => entries.push((
+                VocabEntry {
+                    reading: base_form_reading(info, &base),
+                    base_form: base,
+                    pos: info.pos().to_string(),
+                    count: 1,
+                },
+                i,
+            )),
+        }
+    }
+
+    entries.sort_by(|(a, _), (b, _)| {
+        let key = |entry: &VocabEntry| {
+            entry
+                .reading
+                .clone()
+                .unwrap_or_else(|| entry.base_form.clone())
+        };
+        b.count.cmp(&a.count).then_with(|| key(a).cmp(&key(b)))
+    });
+    entries
+}
+
+// TSV の項目に入れられない文字（タブ・改行）を空白にする
+fn tsv_field(text: &str) -> String {
+    text.replace(['\t', '\r', '\n'], " ").trim().to_string()
+}
+
+// Anki に読み込める TSV（辞書形・読み・品詞・例文）を返す
+// 語は build_vocabulary と同じく辞書形でまとめ、例文は入力のうちその語が最初に出てくる文
+// テキストは一度だけ解析し、語が最初に出てくるトークンの位置からそれを含む文を選ぶ
+pub fn anki_tsv_of_text(text: String) -> Result<String, BunsetsuError> {
+    let token_infos = tokenize_to_infos(&text)?;
+    let sentences = sentence_ranges(&text);
+
+    let mut tsv = String::new();
+    for (entry, first) in vocabulary_of_tokens(&token_infos, &VocabOptions::default()) {
+        let position = token_infos[first].byte_start;
+        let example = sentences
+            .iter()
+            .find(|sentence| sentence.contains(&position))
+            .map(|sentence| &text[sentence.clone()])
+            .unwrap_or("");
+        tsv.push_str(&format!(
+            "{}\t{}\t{}\t{}\n",
+            tsv_field(&entry.base_form),
+            tsv_field(entry.reading.as_deref().unwrap_or("")),
+            tsv_field(&entry.pos),
+            tsv_field(example)
+        ));
+    }
+    Ok(tsv)
+}
+
+// 振り仮名を付ける語（位置は文字単位）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FuriganaSpan {
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+    pub reading: String,
+}
+
+// 指定した文字範囲 [start, end) に掛かる語にだけ振り仮名を付ける（表示中の部分だけを注釈する用）
+// 範囲の端が語の途中にある場合は、その語全体を含める
+pub fn furigana_spans_in_range(
+    text: String,
+    start: usize,
+    end: usize,
+) -> Result<Vec<FuriganaSpan>, BunsetsuError> {
+    // スクロールのたびに長い文書を解析し直さないよう、解析結果のキャッシュを使う
+    let token_infos = tokenize_to_infos(&text)?;
+
+    let mut spans = Vec::new();
+    let mut offset = 0;
+    for info in token_infos.iter() {
+        let token_start = offset;
+        let token_end = offset + info.text.chars().count();
+        offset = token_end;
+
+        if token_end <= start {
+            continue;
+        }
+        if token_start >= end {
+            break;
+        }
+
+        if let Some(reading) = annotation_reading(info) {
+            // 略語の読みはカタカナのまま付ける
+            let reading = if contains_kanji(&info.text) {
+                katakana_to_hiragana(&reading)
+            } else {
+                reading
+            };
+            spans.push(FuriganaSpan {
+                start: token_start,
+                end: token_end,
+                text: info.text.clone(),
+                reading,
+            });
+        }
+    }
+
+    Ok(spans)
+}
+
+// 文節とその主要語（索引用）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PhraseHead {
+    pub phrase: String,
+    pub surface: String,
+    pub base_form: String,
+}
+
+// 自立した内容語（名詞・動詞・形容詞）かどうか
+fn is_content_word(info: &TokenInfo) -> bool {
+    match info.pos() {
+        "名詞" => !matches!(info.pos_detail_1(), Some("非自立" | "接尾")),
+        "動詞" | "形容詞" => info.pos_detail_1() == Some("自立"),
+        _ => false,
+    }
+}
+
+// 文節ごとに最初の自立した内容語を主要語として返す
+// 内容語を含まない文節（記号・助詞だけ）は返さない
+pub fn phrase_heads_in_text(text: String) -> Result<Vec<PhraseHead>, BunsetsuError> {
+    let (token_infos, ranges) = source_segments(&text)?;
+
+    let heads = ranges
+        .into_iter()
+        .filter_map(|range| {
+            let phrase = &token_infos[range];
+            let head = phrase.iter().find(|info| is_content_word(info))?;
+            let base_form = match head.base_form() {
+                Some(base) if base != "*" => base,
+                _ => head.text.as_str(),
+            };
+            Some(PhraseHead {
+                phrase: join_token_texts(phrase),
+                surface: head.text.clone(),
+                base_form: base_form.to_string(),
+            })
+        })
+        .collect();
+
+    Ok(heads)
+}
+
+// 文節と、それを構成するトークン
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bunsetsu {
+    pub surface: String,
+    pub tokens: Vec<WordInfo>,
+    // 主要語（最初の自立した名詞・動詞・形容詞）の品詞。主要語がなければ先頭のトークンの品詞
+    pub head_pos: String,
+}
+
+// 文節の主要語（最初の自立した名詞・動詞・形容詞）。主要語がなければ先頭のトークン
+fn phrase_head(phrase: &[TokenInfo]) -> Option<&TokenInfo> {
+    phrase
+        .iter()
+        .find(|info| is_content_word(info))
+        .or(phrase.first())
+}
+
+// 文節ごとに構成するトークンと主要語の品詞を返す
+pub fn split_text_into_detailed(text: String) -> Result<Vec<Bunsetsu>, BunsetsuError> {
+    let (token_infos, ranges) = source_segments(&text)?;
+
+    Ok(ranges
+        .into_iter()
+        .map(|range| {
+            let phrase = &token_infos[range];
+            let head = phrase_head(phrase);
+            Bunsetsu {
+                surface: join_token_texts(phrase),
+                tokens: word_infos(phrase),
+                head_pos: head.map(|info| info.pos().to_string()).unwrap_or_default(),
+            }
+        })
+        .collect())
+}
+
+// 文節の係り受け。head は係り先の文節の添字（文末の文節と記号だけの文節は None）
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BunsetsuDep {
+    pub index: usize,
+    pub head: Option<usize>,
+    pub surface: String,
+}
+
+// 名詞に係る文節：連体詞（この、大きな）、連体化の「の」、連体形で終わる用言・助動詞（赤い、咲いた、静かな）
+// IPADIC では連体形も基本形になるので、句読点で終わらない基本形は連体修飾とみなす
+fn is_adnominal_phrase(phrase: &[TokenInfo]) -> bool {
+    let Some(last) = phrase.last() else {
+        return false;
+    };
+    match last.pos() {
+        "連体詞" => true,
+        "助詞" => last.pos_detail_1() == Some("連体化"),
+        "動詞" | "形容詞" | "助動詞" => last.conjugation_form().is_some_and(|form| {
+            matches!(form, "基本形" | "連体形") || form.starts_with("体言接続")
+        }),
+        _ => false,
+    }
+}
+
+// 述語を含む文節（動詞・形容詞・助動詞を含む）
+fn is_predicate_phrase(phrase: &[TokenInfo]) -> bool {
+    phrase
+        .iter()
+        .any(|info| matches!(info.pos(), "動詞" | "形容詞" | "助動詞"))
+}
+
+// 右向きに係る単純な規則で係り受けを求める（係り先は同じ文の中の後ろの文節）
+// 連体修飾の文節は次の名詞の文節に、それ以外は次の述語の文節に係る。見つからなければ文の最後の文節に係る
+pub fn parse_dependencies_in_text(text: String) -> Result<Vec<BunsetsuDep>, BunsetsuError> {
+    let (token_infos, ranges) = source_segments(&text)?;
+    let phrases: Vec<&[TokenInfo]> = ranges
+        .into_iter()
+        .map(|range| &token_infos[range])
+        .collect();
+    let surfaces: Vec<String> = phrases
+        .iter()
+        .map(|phrase| join_token_texts(phrase))
+        .collect();
+    let symbol_only = |j: usize| phrases[j].iter().all(|info| info.pos() == "記号");
+
+    let mut deps = Vec::new();
+    for (i, phrase) in phrases.iter().enumerate() {
+        // 係り先の候補は、文末までの記号だけではない文節
+        let sentence_end = (i..phrases.len())
+            .find(|&j| ends_with_sentence_boundary(&surfaces[j]))
+            .unwrap_or(phrases.len() - 1);
+        let candidates: Vec<usize> = (i + 1..=sentence_end)
+            .filter(|&j| !symbol_only(j))
+            .collect();
+
+        let head = if symbol_only(i) {
+            None
+        } else if is_adnominal_phrase(phrase) {
+            candidates
+                .iter()
+                .copied()
+                .find(|&j| phrase_head(phrases[j]).is_some_and(|head| head.pos() == "名詞"))
+                .or(candidates.last().copied())
+        } else {
+            candidates
+                .iter()
+                .copied()
+                .find(|&j| is_predicate_phrase(phrases[j]))
+                .or(candidates.last().copied())
+        };
+
+        deps.push(BunsetsuDep {
+            index: i,
+            head,
+            surface: surfaces[i].clone(),
+        });
+    }
+
+    Ok(deps)
+}
+
+// 正解の文節分割と比べた境界の精度
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SegmentationScore {
+    pub precision: f64,
+    pub recall: f64,
+    pub f1: f64,
+}
+
+// 文節の並びから、文節間の境界の位置（先頭からの文字数）を求める
+// テキストの先頭と末尾は境界に含めない
+fn phrase_boundary_offsets(phrases: &[String]) -> BTreeSet<usize> {
+    let mut offsets = BTreeSet::new();
+    let mut offset = 0;
+    for phrase in phrases.iter().take(phrases.len().saturating_sub(1)) {
+        offset += phrase.chars().count();
+        offsets.insert(offset);
+    }
+    offsets
+}
+
+// 分母が 0 の場合は 1.0 とする（境界が一つもなければ取りこぼし・誤りもない、漢字がなければ読みの不明な漢字もない）
+fn ratio(numerator: usize, denominator: usize) -> f64 {
+    if denominator == 0 {
+        1.0
+    } else {
+        numerator as f64 / denominator as f64
+    }
+}
+
+// 人手で分割した正解の文節列と比べ、境界の適合率・再現率・F値を返す（規則調整の評価用）
+// 正解の文節をつなげたものは入力テキストと一致していなければならない
+pub fn score_segmentation(
+    text: String,
+    gold: Vec<String>,
+) -> Result<SegmentationScore, BunsetsuError> {
+    if gold.concat() != text {
+        return Err(BunsetsuError::InvalidArgument(
+            "正解の文節をつなげたものが入力テキストと一致しません".to_string(),
+        ));
+    }
+
+    // 正解の位置は入力テキストでの位置なので、予測も元の表記のまま分ける
+    let predicted: Vec<String> = offset_phrases(text, &active_boundary_rules())?
+        .into_iter()
+        .map(|(phrase, _, _)| phrase)
+        .collect();
+    let predicted = phrase_boundary_offsets(&predicted);
+    let expected = phrase_boundary_offsets(&gold);
+    let matched = predicted.intersection(&expected).count();
+
+    let precision = ratio(matched, predicted.len());
+    let recall = ratio(matched, expected.len());
+    let f1 = if precision + recall == 0.0 {
+        0.0
+    } else {
+        2.0 * precision * recall / (precision + recall)
+    };
+
+    Ok(SegmentationScore {
+        precision,
+        recall,
+        f1,
+    })
+}
+
+// IOB2 形式で文節タグを付けたトークン
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaggedToken {
+    pub text: String,
+    // 文節の先頭なら "B"、文節の途中なら "I"
+    pub tag: String,
+}
+
+// トークンごとに IOB2 形式の文節タグを付ける（分割器の学習データ書き出し用）
+pub fn iob_tags_in_text(text: String) -> Result<Vec<TaggedToken>, BunsetsuError> {
+    let (token_infos, ranges) = source_segments(&text)?;
+    let starts: BTreeSet<usize> = ranges.iter().map(|range| range.start).collect();
+
+    let tagged = token_infos
+        .iter()
+        .enumerate()
+        .map(|(i, info)| {
+            // 先頭のトークンと、文節の最初のトークンが文節の始まり
+            let begins = i == 0 || starts.contains(&i);
+            TaggedToken {
+                text: info.text.clone(),
+                tag: if begins { "B" } else { "I" }.to_string(),
+            }
+        })
+        .collect();
+
+    Ok(tagged)
+}
+
+// 読み上げ時間の見積もりに使う速さと間（秒）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpeechDurationOptions {
+    // 1 モーラあたりの時間
+    pub seconds_per_mora: f64,
+    // 文節の境界の間
+    pub phrase_pause_seconds: f64,
+    // 文の境界（。！？の後）の間
+    pub sentence_pause_seconds: f64,
+}
+
+impl Default for SpeechDurationOptions {
+    fn default() -> Self {
+        SpeechDurationOptions {
+            seconds_per_mora: 0.15,
+            phrase_pause_seconds: 0.1,
+            sentence_pause_seconds: 0.4,
+        }
+    }
+}
+
+// テキスト全体を読み上げるのにかかる時間（秒）を見積もる
+// 文節ごとのモーラ数から求めた時間に、文節・文の境界の間を足す
+pub fn estimate_text_duration(
+    text: String,
+    options: &SpeechDurationOptions,
+) -> Result<f64, BunsetsuError> {
+    let (token_infos, ranges) = source_segments(&text)?;
+
+    let mut seconds = 0.0;
+    for (i, range) in ranges.iter().enumerate() {
+        let phrase = &token_infos[range.clone()];
+        let mora: usize = phrase.iter().map(token_mora).sum();
+        seconds += mora as f64 * options.seconds_per_mora;
+
+        // 最後の文節の後ろには間を入れない
+        if i + 1 < ranges.len() {
+            seconds += if ends_with_sentence_boundary(&join_token_texts(phrase)) {
+                options.sentence_pause_seconds
+            } else {
+                options.phrase_pause_seconds
+            };
+        }
+    }
+
+    Ok(seconds)
+}
+
+// 一つずつ表示する（RSVP）ためのまとまりの作り方
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RsvpOptions {
+    // 1 回に表示する最大の文字数。これより長い文節はトークンの切れ目で分ける
+    pub max_chars: usize,
+    // 助詞だけの文節（「」の後ろの「を」など）を前のまとまりに付ける
+    pub merge_short_particles: bool,
+}
+
+impl Default for RsvpOptions {
+    fn default() -> Self {
+        RsvpOptions {
+            max_chars: 12,
+            merge_short_particles: true,
+        }
+    }
+}
+
+// 一度に表示するまとまりと、その表示時間（ミリ秒）
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RsvpChunk {
+    pub text: String,
+    pub display_ms: u64,
+}
+
+// まとまりごとの表示時間：基本の時間に 1 文字あたりの時間を足す
+const RSVP_BASE_MS: u64 = 150;
+const RSVP_MS_PER_CHAR: u64 = 60;
+
+// 分けられない長いトークンは一目で読めないので、max_chars を超えた分の文字に倍の時間をかける
+fn rsvp_display_ms(text: &str, max_chars: usize) -> u64 {
+    let chars = text.chars().filter(|c| !c.is_whitespace()).count();
+    let overflow = chars.saturating_sub(max_chars);
+    RSVP_BASE_MS + (chars + overflow) as u64 * RSVP_MS_PER_CHAR
+}
+
+// 文節を元に、一つずつ表示するまとまりに分ける
+pub fn chunk_text_for_rsvp(
+    text: String,
+    options: &RsvpOptions,
+) -> Result<Vec<RsvpChunk>, BunsetsuError> {
+    let (token_infos, ranges) = source_segments(&text)?;
+
+    let mut pieces: Vec<String> = Vec::new();
+    for range in ranges {
+        let phrase = &token_infos[range];
+        if options.merge_short_particles && phrase.iter().all(|info| info.pos() == "助詞") {
+            if let Some(last) = pieces.last_mut() {
+                last.push_str(&join_token_texts(phrase));
+                continue;
+            }
+        }
+
+        // 長い文節はトークンの切れ目で分ける（1 トークンで max_chars を超える場合はそのまま）
+        let mut piece = String::new();
+        for info in phrase {
+            if !piece.is_empty()
+                && piece.chars().count() + info.text.chars().count() > options.max_chars
+            {
+                pieces.push(std::mem::take(&mut piece));
+            }
+            piece.push_str(&info.text);
+        }
+        if !piece.is_empty() {
+            pieces.push(piece);
+        }
+    }
+
+    Ok(pieces
+        .into_iter()
+        .map(|text| RsvpChunk {
+            display_ms: rsvp_display_ms(&text, options.max_chars),
+            text,
+        })
+        .collect())
+}
+
+// 品詞の大分類（語彙一覧の分類名）。記号は対象外
+fn coarse_pos_category(info: &TokenInfo) -> Option<&'static str> {
+    let category = match info.pos() {
+        "名詞" => "nouns",
+        "動詞" => "verbs",
+        "形容詞" => "adjectives",
+        "副詞" => "adverbs",
+        "連体詞" => "adnominals",
+        "接続詞" => "conjunctions",
+        "感動詞" => "interjections",
+        "助詞" => "particles",
+        "助動詞" => "auxiliaries",
+        "接頭詞" => "prefixes",
+        "記号" => return None,
+        _ => "others",
+    };
+    Some(category)
+}
+
+// 品詞の大分類ごとに語の原形をまとめる（分類別の語彙一覧用）
+// 同じ原形は分類ごとに一度だけ、出現順に並べる
+pub fn group_words_by_pos(text: String) -> Result<BTreeMap<String, Vec<String>>, BunsetsuError> {
+    let token_infos = tokenize_to_infos(&text)?;
+    let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    for info in token_infos.iter() {
+        let Some(category) = coarse_pos_category(info) else {
+            continue;
+        };
+        let base = match info.base_form() {
+            Some(base) if base != "*" => base,
+            _ => info.text.as_str(),
+        };
+        let words = groups.entry(category.to_string()).or_default();
+        if !words.iter().any(|word| word == base) {
+            words.push(base.to_string());
+        }
+    }
+
+    Ok(groups)
+}
+
+// 難易度の算出に使う 1 文の長さ（記号を除く文字数）の上限。これ以上の長さは同じ扱いにする
+const DIFFICULTY_SENTENCE_LENGTH: f64 = 50.0;
+
+// 難易度ラベルのしきい値
+// score < 0.25 → 初級、0.25 <= score < 0.45 → 中級、0.45 <= score → 上級
+const INTERMEDIATE_THRESHOLD: f64 = 0.25;
+const ADVANCED_THRESHOLD: f64 = 0.45;
+
+// 文章の難易度（0.0〜1.0）
+// 漢字の割合（7 割）と 1 文の平均の長さ（3 割）から求める
+pub fn difficulty_score(text: String) -> Result<f64, BunsetsuError> {
+    let token_infos = tokenize_to_infos(&text)?;
+
+    let words: Vec<&TokenInfo> = token_infos
+        .iter()
+        .filter(|info| info.pos() != "記号")
+        .collect();
+    let chars = words
+        .iter()
+        .map(|info| info.text.chars().count())
+        .sum::<usize>();
+    if chars == 0 {
+        return Ok(0.0);
+    }
+    let kanji = words
+        .iter()
+        .map(|info| info.text.chars().filter(|c| is_kanji_char(*c)).count())
+        .sum::<usize>();
+
+    let sentences = split_token_infos_into_sentences(&token_infos).len().max(1);
+    let average_length = chars as f64 / sentences as f64;
+
+    let kanji_ratio = kanji as f64 / chars as f64;
+    let length_ratio = (average_length / DIFFICULTY_SENTENCE_LENGTH).min(1.0);
+    Ok(0.7 * kanji_ratio + 0.3 * length_ratio)
+}
+
+// 難易度からラベル（初級・中級・上級）を決める
+pub fn difficulty_label_for_score(score: f64) -> &'static str {
+    if score < INTERMEDIATE_THRESHOLD {
+        "初級"
+    } else if score < ADVANCED_THRESHOLD {
+        "中級"
+    } else {
+        "上級"
+    }
+}
+
+pub fn difficulty_label_of_text(text: String) -> Result<String, BunsetsuError> {
+    Ok(difficulty_label_for_score(difficulty_score(text)?).to_string())
+}
+
+// 文章全体の読みやすさ
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Readability {
+    pub sentence_count: usize,
+    // 文節の平均の長さ（記号を除く文字数）
+    pub average_bunsetsu_length: f64,
+    // 1 文あたりの平均の文節数
+    pub average_bunsetsu_per_sentence: f64,
+    // 記号を除く文字のうち漢字の割合
+    pub kanji_ratio: f64,
+    // 長い文（LONG_SENTENCE_LENGTH 文字以上）の割合
+    pub long_sentence_ratio: f64,
+    // 読みやすさ（0〜100。大きいほど読みやすい）
+    pub ease: f64,
+}
+
+// 長い文とみなす文字数（記号を除く）
+const LONG_SENTENCE_LENGTH: usize = 40;
+// 読みやすさの算出で、これ以上は同じ扱いにする文節の平均の長さと漢字の割合
+const READABILITY_BUNSETSU_LENGTH: f64 = 8.0;
+const READABILITY_KANJI_RATIO: f64 = 0.5;
+
+// テキストを文に分ける。文末記号（is_sentence_terminator）と改行で区切り、続く文末記号・「…」・閉じ括弧も同じ文に含める
+// 括弧（「」『』（））の中の文末記号では区切らない（「…だ。」と言った。は一文）
+// 文末記号がなければ全体で一文
+fn split_sentences(text: &str) -> Vec<&str> {
+    sentence_ranges(text)
+        .into_iter()
+        .map(|range| &text[range])
+        .filter(|sentence| !sentence.trim().is_empty())
+        .collect()
+}
+
+// 文の範囲（バイト単位）。空白だけの文も含め、範囲は隙間なく並んでテキスト全体を覆う
+fn sentence_ranges(text: &str) -> Vec<Range<usize>> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut depth = 0usize;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '「' | '『' | '（' => depth += 1,
+            '」' | '』' | '）' => depth = depth.saturating_sub(1),
+            // 括弧が閉じられていなくても改行で文を終える
+            '\n' => {
+                depth = 0;
+                sentences.push(start..i + 1);
+                start = i + 1;
+            }
+            c if depth == 0 && is_sentence_terminator(c) => {
+                let mut end = i + c.len_utf8();
+                while let Some(&(j, next)) = chars.peek().filter(|(_, next)| {
+                    is_sentence_terminator(*next) || matches!(next, '…' | '」' | '』' | '）')
+                }) {
+                    end = j + next.len_utf8();
+                    chars.next();
+                }
+                sentences.push(start..end);
+                start = end;
+            }
+            _ => {}
+        }
+    }
+    if start < text.len() {
+        sentences.push(start..text.len());
+    }
+    sentences
+}
+
+// テキストを文に分けて返す（文末記号は文に含める）
+pub fn split_text_into_sentences(text: String) -> Vec<String> {
+    split_sentences(&text)
+        .into_iter()
+        .map(str::to_string)
+        .collect()
+}
+
+// 段落（空行で区切られたまとまり）ごとに文節に分ける（縦書きなどで段落の構成を保つ用）
+// 改行・字下げ・空行は空白だけの要素として元のまま残すので、すべてつなげると元のテキストになる
+// 例: "今日は晴れ。\n\n明日は雨。" → [["今日は", "晴れ。", "\n\n"], ["明日は", "雨。"]]
+pub fn split_text_into_paragraphs(text: String) -> Result<Vec<Vec<String>>, BunsetsuError> {
+    let mut paragraphs = Vec::new();
+    let mut paragraph: Vec<String> = Vec::new();
+    let mut after_blank_line = false;
+
+    for line in text.split_inclusive('\n') {
+        if line.trim().is_empty() {
+            // 空行は直前の改行につなげ、次の行から新しい段落にする
+            match paragraph.last_mut() {
+                Some(last) if last.trim().is_empty() => last.push_str(line),
+                _ => paragraph.push(line.to_string()),
+            }
+            after_blank_line = true;
+            continue;
+        }
+        if after_blank_line && !paragraph.is_empty() {
+            paragraphs.push(std::mem::take(&mut paragraph));
+        }
+        after_blank_line = false;
+
+        let content = line.trim_start();
+        let indent = &line[..line.len() - content.len()];
+        let body = content.trim_end();
+        let trailing = &content[body.len()..];
+
+        if !indent.is_empty() {
+            paragraph.push(indent.to_string());
+        }
+        paragraph.extend(split_phrases(body.to_string())?);
+        if !trailing.is_empty() {
+            paragraph.push(trailing.to_string());
+        }
+    }
+    if !paragraph.is_empty() {
+        paragraphs.push(paragraph);
+    }
+
+    Ok(paragraphs)
+}
+
+// 漢字・仮名・英数字の文字数（記号と空白を除く）
+fn count_letters(text: &str) -> usize {
+    text.chars()
+        .filter(|c| is_kanji_char(*c) || is_kana(*c) || is_latin_or_digit(*c))
+        .count()
+}
+
+// 文ごとに文節に分けて集計し、文章全体の読みやすさを求める
+// 読みやすさは漢字の割合（5 割）・文節の平均の長さ（3 割）・長い文の割合（2 割）から求める
+pub fn compute_readability(text: String) -> Result<Readability, BunsetsuError> {
+    let sentences = split_sentences(&text);
+    if sentences.is_empty() {
+        return Ok(Readability::default());
+    }
+
+    let (mut letters, mut kanji, mut phrases, mut long_sentences) = (0, 0, 0, 0);
+    for sentence in &sentences {
+        let sentence_letters = count_letters(sentence);
+        letters += sentence_letters;
+        kanji += sentence.chars().filter(|c| is_kanji_char(*c)).count();
+        // 句点だけの文節などは数えない
+        phrases += split_phrases(sentence.to_string())?
+            .iter()
+            .filter(|phrase| count_letters(phrase) > 0)
+            .count();
+        if sentence_letters >= LONG_SENTENCE_LENGTH {
+            long_sentences += 1;
+        }
+    }
+
+    // 記号だけの文章では 0 にする
+    let average = |count: usize, total: usize| {
+        if total == 0 {
+            0.0
+        } else {
+            count as f64 / total as f64
+        }
+    };
+    let average_bunsetsu_length = average(letters, phrases);
+    let kanji_ratio = average(kanji, letters);
+    let long_sentence_ratio = average(long_sentences, sentences.len());
+    let difficulty = 0.5 * (kanji_ratio / READABILITY_KANJI_RATIO).min(1.0)
+        + 0.3 * (average_bunsetsu_length / READABILITY_BUNSETSU_LENGTH).min(1.0)
+        + 0.2 * long_sentence_ratio;
+
+    Ok(Readability {
+        sentence_count: sentences.len(),
+        average_bunsetsu_length,
+        average_bunsetsu_per_sentence: average(phrases, sentences.len()),
+        kanji_ratio,
+        long_sentence_ratio,
+        ease: 100.0 * (1.0 - difficulty),
+    })
+}
+
+// 黙読の速さの既定値（仮名で 1 分間に読む文字数）
+pub const DEFAULT_CHARS_PER_MINUTE: f64 = 500.0;
+
+// 文ごとの難易度・黙読時間・文節数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SentenceAnalysis {
+    pub text: String,
+    // difficulty_score と同じ 0.0〜1.0 の難易度
+    pub difficulty: f64,
+    pub reading_seconds: f64,
+    pub phrase_count: usize,
+}
+
+// テキストを文に分け、文ごとに難易度・黙読時間・文節数をまとめて返す
+pub fn analyze_sentences_in_text(
+    text: String,
+    chars_per_minute: f64,
+) -> Result<Vec<SentenceAnalysis>, BunsetsuError> {
+    split_sentences(&text)
+        .into_iter()
+        .map(|sentence| {
+            Ok(SentenceAnalysis {
+                text: sentence.to_string(),
+                difficulty: difficulty_score(sentence.to_string())?,
+                reading_seconds: estimate_text_reading_time(
+                    sentence.to_string(),
+                    chars_per_minute,
+                )?
+                .seconds,
+                phrase_count: split_phrases(sentence.to_string())?.len(),
+            })
+        })
+        .collect()
+}
+
+// トークンごとの漢字の難易度
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenDifficulty {
+    pub surface: String,
+    // トークンに含まれる漢字のうち最も難しいもの。漢字を含まなければ None
+    pub level: Option<KanjiLevel>,
+}
+
+// トークンごとに、含まれる漢字の学年（教育漢字）・常用漢字・常用外の別を返す
+// 「々」などの記号は漢字として数えない
+pub fn token_difficulties_in_text(text: String) -> Result<Vec<TokenDifficulty>, BunsetsuError> {
+    let token_infos = tokenize_to_infos(&text)?;
+
+    Ok(token_infos
+        .iter()
+        .map(|info| TokenDifficulty {
+            surface: info.text.clone(),
+            level: hardest_kanji_level(&info.text),
+        })
+        .collect())
+}
+
+// 含まれる漢字のうち最も難しいものの段階。漢字を含まなければ None
+fn hardest_kanji_level(text: &str) -> Option<KanjiLevel> {
+    text.chars()
+        .filter(|c| is_kanji_char(*c) && !matches!(c, '々' | '〆' | 'ヶ'))
+        .map(kanji_level)
+        .max()
+}
+
+// min_grade 年生以降に習う漢字（常用漢字・常用外を含む）を含むなら true
+// ルビの HTML と印刷用の括弧書きの読みで同じ基準を使う
+fn needs_reading_for_grade(text: &str, min_grade: u8) -> bool {
+    hardest_kanji_level(text).is_some_and(|level| level >= KanjiLevel::Grade(min_grade))
+}
+
+// 印刷用の整形のオプション
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PrintOptions {
+    // 1 行の最大の文字数（0 なら折り返さない）
+    pub line_width: usize,
+    // 文節の間に入れる文字列（分かち書き）。空なら詰めて書く
+    pub phrase_separator: String,
+    // min_grade 年生以降に習う漢字（常用漢字・常用外を含む）を含む語の後ろに読みを括弧で付ける。None なら付けない
+    pub furigana_min_grade: Option<u8>,
+}
+
+impl Default for PrintOptions {
+    fn default() -> Self {
+        PrintOptions {
+            line_width: 40,
+            phrase_separator: "　".to_string(),
+            furigana_min_grade: None,
+        }
+    }
+}
+
+// 文節を分かち書きし、難しい漢字に括弧書きの読みを付け、行の幅で折り返したテキストを返す
+// 元の改行は段落の区切りとして残す。1 行に収まらない文節は文字単位で折り返す
+pub fn format_text_for_print(
+    text: String,
+    options: &PrintOptions,
+) -> Result<String, BunsetsuError> {
+    let needs_reading = |info: &TokenInfo| {
+        options
+            .furigana_min_grade
+            .is_some_and(|min_grade| needs_reading_for_grade(&info.text, min_grade))
+    };
+
+    let mut lines: Vec<String> = Vec::new();
+    for paragraph in text.lines() {
+        let (token_infos, ranges) = source_segments(paragraph.trim())?;
+        let mut line = String::new();
+        for range in ranges {
+            let phrase_tokens = &token_infos[range];
+            let phrase: String = phrase_tokens
+                .iter()
+                .map(
+                    |info| match word_reading(info).filter(|_| needs_reading(info)) {
+                        Some(reading) => format!("{}（{}）", info.text, reading),
+                        None => info.text.clone(),
+                    },
+                )
+                .collect();
+            // 句読点だけの文節は前の文節に詰めて付ける
+            let separator =
+                if line.is_empty() || phrase_tokens.iter().all(|info| info.pos() == "記号") {
+                    ""
+                } else {
+                    options.phrase_separator.as_str()
+                };
+
+            let joined_width: usize = [line.as_str(), separator, phrase.as_str()]
+                .iter()
+                .map(|part| part.chars().count())
+                .sum();
+            if options.line_width == 0 || joined_width <= options.line_width {
+                line.push_str(separator);
+                line.push_str(&phrase);
+                continue;
+            }
+
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            // 1 行に収まらない文節は文字単位で折り返す
+            let chars: Vec<char> = phrase.chars().collect();
+            let mut pieces = chars.chunks(options.line_width).peekable();
+            while let Some(piece) = pieces.next() {
+                let piece: String = piece.iter().collect();
+                if pieces.peek().is_some() {
+                    lines.push(piece);
+                } else {
+                    line = piece;
+                }
+            }
+        }
+        lines.push(line);
+    }
+
+    Ok(lines.join("\n"))
+}
+
+// 文節の文字種の分類（文節ごとに色分けするため）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PhraseScript {
+    pub text: String,
+    // "kanji"（ほぼ漢字）・"kana"（ほぼ仮名）・"latin"（ほぼ英数字）・"mixed"（混在）
+    // 記号だけの文節は "other"
+    pub script: String,
+}
+
+// 文節の文字（記号を除く）のうち、この割合以上を占める文字種をその文節の文字種とする
+const DOMINANT_SCRIPT_RATIO: f64 = 0.8;
+
+fn classify_phrase_script(phrase: &str) -> &'static str {
+    let (mut kanji, mut kana, mut latin) = (0, 0, 0);
+    for c in phrase.chars() {
+        match c {
+            c if is_kanji_char(c) => kanji += 1,
+            c if is_kana(c) => kana += 1,
+            c if is_latin_or_digit(c) => latin += 1,
+            _ => {}
+        }
+    }
+
+    let total = kanji + kana + latin;
+    if total == 0 {
+        return "other";
+    }
+    let dominant = |count: usize| ratio(count, total) >= DOMINANT_SCRIPT_RATIO;
+    if dominant(kanji) {
+        "kanji"
+    } else if dominant(kana) {
+        "kana"
+    } else if dominant(latin) {
+        "latin"
+    } else {
+        "mixed"
+    }
+}
+
+// 文節ごとに主な文字種を返す
+pub fn phrase_scripts_in_text(text: String) -> Result<Vec<PhraseScript>, BunsetsuError> {
+    Ok(split_phrases(text)?
+        .into_iter()
+        .map(|phrase| PhraseScript {
+            script: classify_phrase_script(&phrase).to_string(),
+            text: phrase,
+        })
+        .collect())
+}
+
+// プレビュー用に、文節を max_chars 文字（書記素クラスタ単位）までつないで返す
+// 入りきらない文節は含めず、末尾に「…」を付ける。最初の文節も入りきらない場合は文字単位で切る
+// 濁点の結合文字などが文節の境界で分かれていても、書記素クラスタの途中では切らない
+// 正規化や縮約形の展開を有効にしていても、プレビューは入力どおりの表記で返す
+pub fn truncate_preview_text(text: String, max_chars: usize) -> Result<String, BunsetsuError> {
+    let phrases: Vec<String> = offset_phrases(text, &active_boundary_rules())?
+        .into_iter()
+        .map(|(phrase, _, _)| phrase)
+        .collect();
+    let joined = phrases.concat();
+
+    let grapheme_starts: Vec<usize> = joined.grapheme_indices(true).map(|(i, _)| i).collect();
+    let Some(&limit) = grapheme_starts.get(max_chars) else {
+        return Ok(joined);
+    };
+
+    let mut cut = 0;
+    let mut end = 0;
+    for phrase in &phrases {
+        end += phrase.len();
+        if end > limit {
+            break;
+        }
+        if grapheme_starts.binary_search(&end).is_ok() {
+            cut = end;
+        }
+    }
+    if cut == 0 {
+        cut = limit;
+    }
+
+    Ok(format!("{}…", &joined[..cut]))
+}
+
+// 文節が節を終えるかどうか
+// 末尾の記号・終助詞を除いた最後の語が接続助詞（疲れたから、いいけどね）なら節の終わり
+// 格助詞の「から」（東京から）などは節を終えない
+fn ends_clause(phrase: &[TokenInfo]) -> bool {
+    if ends_with_sentence_boundary(&join_token_texts(phrase)) {
+        return true;
+    }
+    last_word_before_final_particles(phrase)
+        .is_some_and(|info| info.pos() == "助詞" && info.pos_detail_1() == Some("接続助詞"))
+}
+
+// テキストを節（接続助詞・文末で区切った文節のまとまり）に分ける
+pub fn split_text_into_clauses(text: String) -> Result<Vec<String>, BunsetsuError> {
+    let (token_infos, ranges) = source_segments(&text)?;
+
+    let mut clauses = Vec::new();
+    let mut clause = String::new();
+    for range in ranges {
+        let phrase = &token_infos[range];
+        clause.push_str(&join_token_texts(phrase));
+        if ends_clause(phrase) {
+            clauses.push(std::mem::take(&mut clause));
+        }
+    }
+    if !clause.is_empty() {
+        clauses.push(clause);
+    }
+
+    Ok(clauses)
+}
+
+// トークンの表記に含まれる文字種ごとの文字数
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TokenCharCounts {
+    pub text: String,
+    pub kanji: usize,
+    pub hiragana: usize,
+    // 長音記号「ー」もカタカナに数える
+    pub katakana: usize,
+    // 半角・全角の英字
+    pub latin: usize,
+    // 半角・全角の数字
+    pub digit: usize,
+}
+
+// トークンごとに文字種（漢字・ひらがな・カタカナ・英字・数字）の文字数を数える
+pub fn token_char_counts_in_text(text: String) -> Result<Vec<TokenCharCounts>, BunsetsuError> {
+    Ok(token_char_counts(&tokenize_to_infos(&text)?))
+}
+
+fn token_char_counts(token_infos: &[TokenInfo]) -> Vec<TokenCharCounts> {
+    token_infos
+        .iter()
+        .map(|info| {
+            let mut counts = TokenCharCounts {
+                text: info.text.clone(),
+                ..Default::default()
+            };
+            for c in info.text.chars() {
+                match c {
+                    c if is_kanji_char(c) => counts.kanji += 1,
+                    'ぁ'..='ゖ' => counts.hiragana += 1,
+                    'ァ'..='ヺ' | 'ー' => counts.katakana += 1,
+                    '0'..='9' | '０'..='９' => counts.digit += 1,
+                    c if is_latin_or_digit(c) => counts.latin += 1,
+                    _ => {}
+                }
+            }
+            counts
+        })
+        .collect()
+}
+
+// 黙読にかかる時間の見積もり
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadingTime {
+    pub seconds: f64,
+    pub token_count: usize,
+    // 空白を除いた文字数
+    pub char_count: usize,
+}
+
+// 漢字は読み取りに時間がかかるので、仮名などの何文字分として数えるか
+const KANJI_READING_WEIGHT: f64 = 2.0;
+
+// 黙読にかかる時間を見積もる
+// chars_per_minute は仮名で 1 分間に読める文字数。漢字は KANJI_READING_WEIGHT 文字分として数える
+pub fn estimate_text_reading_time(
+    text: String,
+    chars_per_minute: f64,
+) -> Result<ReadingTime, BunsetsuError> {
+    if chars_per_minute.is_nan() || chars_per_minute <= 0.0 {
+        return Err(BunsetsuError::InvalidArgument(format!(
+            "1 分間に読む文字数は正の数にしてください: {}",
+            chars_per_minute
+        )));
+    }
+
+    let token_infos = tokenize_to_infos(&text)?;
+    let char_count = text.chars().filter(|c| !c.is_whitespace()).count();
+    let kanji: usize = token_char_counts(&token_infos)
+        .iter()
+        .map(|counts| counts.kanji)
+        .sum();
+
+    let weighted = (char_count - kanji) as f64 + kanji as f64 * KANJI_READING_WEIGHT;
+    Ok(ReadingTime {
+        seconds: weighted / chars_per_minute * 60.0,
+        token_count: token_infos.len(),
+        char_count,
+    })
+}
+
+// 漢字のうち、読みの分かる語に含まれるものの割合（0.0〜1.0。自動の振り仮名がどこまで付くかの目安）
+// 辞書にない語（未知語）には読みがないので数えない。漢字がなければ 1.0
+pub fn reading_coverage_of_text(text: String) -> Result<f64, BunsetsuError> {
+    let token_infos = tokenize_to_infos(&text)?;
+
+    let mut kanji = 0;
+    let mut covered = 0;
+    for info in token_infos.iter() {
+        let count = info.text.chars().filter(|c| is_kanji_char(*c)).count();
+        kanji += count;
+        if info.reading().is_some_and(|reading| reading != "*") {
+            covered += count;
+        }
+    }
+
+    Ok(ratio(covered, kanji))
+}
+
+// 読みのない漢字を含む語と、テキスト中の出現位置（文字単位の開始・終了）
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MissingReading {
+    pub text: String,
+    pub positions: Vec<(usize, usize)>,
+}
+
+// 辞書にない（読みのない）漢字を含む語を、最初に出てきた順に重複なく返す（手で振り仮名を付ける語の一覧用）
+pub fn missing_readings_in_text(text: String) -> Result<Vec<MissingReading>, BunsetsuError> {
+    let token_infos = tokenize_to_infos(&text)?;
+
+    let mut missing: Vec<MissingReading> = Vec::new();
+    let mut offset = 0;
+    for info in token_infos.iter() {
+        let start = offset;
+        offset += info.text.chars().count();
+
+        if !contains_kanji(&info.text) || info.reading().is_some_and(|reading| reading != "*") {
+            continue;
+        }
+        match missing.iter_mut().find(|entry| entry.text == info.text) {
+            Some(entry) => entry.positions.push((start, offset)),
+            None => missing.push(MissingReading {
+                text: info.text.clone(),
+                positions: vec![(start, offset)],
+            }),
+        }
+    }
+
+    Ok(missing)
+}
+
+// トークンの切り方が曖昧かどうか（デバッグ用）
+// alternatives は長い語を分けやすくした解析で同じ範囲がどう切られたか
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TokenAmbiguity {
+    pub text: String,
+    pub pos: String,
+    pub ambiguous: bool,
+    pub alternatives: Vec<String>,
+}
+
+// lindera は他の解析の候補やコストの差を返さないので、長い語に罰則を掛ける分解モードの結果と比べ、
+// 切り方の変わるトークン（関西国際空港 → 関西／国際／空港）を曖昧とみなす
+// 分解モードのトークナイザは初回の呼び出しで読み込むので、その分のメモリを使う
+pub fn token_ambiguities_in_text(text: String) -> Result<Vec<TokenAmbiguity>, BunsetsuError> {
+    let loaded = get_tokenizer().map_err(|e| BunsetsuError::TokenizerInit(e.to_string()))?;
+    let token_infos =
+        tokenize_with(&loaded, &text).map_err(|e| BunsetsuError::Tokenize(e.to_string()))?;
+    let decompose = get_decompose_tokenizer(&loaded)
+        .map_err(|e| BunsetsuError::TokenizerInit(e.to_string()))?;
+
+    // 分解モードのトークンの位置（文字単位の開始・終了）
+    let mut decomposed = Vec::new();
+    let mut offset = 0;
+    for info in
+        tokenize_with(&decompose, &text).map_err(|e| BunsetsuError::Tokenize(e.to_string()))?
+    {
+        let start = offset;
+        offset += info.text.chars().count();
+        decomposed.push((start, offset, info.text));
+    }
+
+    let mut ambiguities = Vec::new();
+    let mut offset = 0;
+    for info in token_infos.iter() {
+        let start = offset;
+        offset += info.text.chars().count();
+        let alternatives: Vec<String> = decomposed
+            .iter()
+            .filter(|(s, e, _)| *s >= start && *e <= offset)
+            .map(|(_, _, text)| text.clone())
+            .collect();
+        ambiguities.push(TokenAmbiguity {
+            text: info.text.clone(),
+            pos: info.pos().to_string(),
+            // 分解モードで同じ範囲が一つのトークンにならなければ曖昧
+            ambiguous: alternatives.len() != 1,
+            alternatives,
+        });
+    }
+
+    Ok(ambiguities)
+}
+
+// 隣り合うトークンの品詞の組（前の品詞 → 後ろの品詞）ごとの出現回数（境界判定のデバッグ用）
+pub fn pos_transitions_in_text(
+    text: String,
+) -> Result<BTreeMap<String, BTreeMap<String, usize>>, BunsetsuError> {
+    let token_infos = tokenize_to_infos(&text)?;
+    let mut matrix: BTreeMap<String, BTreeMap<String, usize>> = BTreeMap::new();
+
+    for pair in token_infos.windows(2) {
+        *matrix
+            .entry(pair[0].pos().to_string())
+            .or_default()
+            .entry(pair[1].pos().to_string())
+            .or_default() += 1;
+    }
+
+    Ok(matrix)
+}
+
+// ら抜き言葉（食べれる、来れる）を動詞と「れる」をつないだ表記で返す
+pub fn ra_nuki_forms_in_text(text: String) -> Result<Vec<String>, BunsetsuError> {
+    let token_infos = tokenize_to_infos(&text)?;
+
+    Ok(token_infos
+        .windows(2)
+        .filter(|pair| is_ra_nuki(&pair[0], &pair[1]))
+        .map(|pair| format!("{}{}", pair[0].text, pair[1].text))
+        .collect())
+}
+
+// 文節と元テキストでの位置
+type PhraseOffsets = Arc<Vec<(String, usize, usize)>>;
+
+// 開いている文書ごとの解析結果（文書 ID → 文節と元テキストでの位置）
+static DOCUMENTS: Mutex<BTreeMap<String, PhraseOffsets>> = Mutex::new(BTreeMap::new());
+
+// 文書を解析して文書 ID で保持する（同じ ID の文書は置き換える）
+// 重い解析を表示の前に済ませておき、スクロール時は保持した結果から取り出す
+pub fn prepare_document(doc_id: String, text: String) -> Result<(), BunsetsuError> {
+    // 解析中はロックを持たない
+    let phrases = Arc::new(split_text_with_offsets(text)?);
+    DOCUMENTS
+        .lock()
+        .map_err(|e| BunsetsuError::Internal(e.to_string()))?
+        .insert(doc_id, phrases);
+    Ok(())
+}
+
+// 保持している文書から、文字範囲 [start, end) に掛かる文節を返す
+pub fn document_phrases_in_range(
+    doc_id: &str,
+    start: usize,
+    end: usize,
+) -> Result<Vec<(String, usize, usize)>, BunsetsuError> {
+    let phrases = DOCUMENTS
+        .lock()
+        .map_err(|e| BunsetsuError::Internal(e.to_string()))?
+        .get(doc_id)
+        .cloned()
+        .ok_or_else(|| BunsetsuError::UnknownDocument(doc_id.to_string()))?;
+
+    Ok(phrases
+        .iter()
+        .filter(|(_, phrase_start, phrase_end)| *phrase_end > start && *phrase_start < end)
+        .cloned()
+        .collect())
+}
+
+// 保持している文書の解析結果を破棄する
+pub fn release_document(doc_id: &str) -> Result<(), BunsetsuError> {
+    DOCUMENTS
+        .lock()
+        .map_err(|e| BunsetsuError::Internal(e.to_string()))?
+        .remove(doc_id);
+    Ok(())
+}
+
+// テスト用のmain関数（必要に応じてコメントアウトまたは削除）
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Barrier, MutexGuard, PoisonError};
+
+    // 共有トークナイザを選び直す・置き換える・破棄するテストは、このロックを取ってから動かす
+    static TOKENIZER_LIFECYCLE: Mutex<()> = Mutex::new(());
+
+    fn lock_tokenizer_lifecycle() -> MutexGuard<'static, ()> {
+        TOKENIZER_LIFECYCLE
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    #[test]
+    fn test_bunsetsu_split() -> Result<(), Box<dyn std::error::Error>> {
+        let text = "人間は文章を読む時、滑らかに文字を読んでいる訳ではなく、「１点を見つめる」という事と「高速に視線を移動する」という事を繰り返しています。".to_string();
+        let bunsetsu = split_text_into_bunsetsu(text)?;
+
+        for b in &bunsetsu {
+            print!("{} / ", b);
+        }
+        println!();
+
+        assert!(!bunsetsu.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_adjective_te_splits_clause() -> Result<(), Box<dyn std::error::Error>> {
+        let bunsetsu = split_text_into_bunsetsu("高くて買えない".to_string())?;
+        assert_eq!(bunsetsu, vec!["高くて", "買えない"]);
+
+        let bunsetsu = split_text_into_bunsetsu("美しくて、優しい".to_string())?;
+        assert_eq!(bunsetsu, vec!["美しくて、", "優しい"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_verb_te_auxiliary_merges() -> Result<(), Box<dyn std::error::Error>> {
+        let bunsetsu = split_text_into_bunsetsu("見ていく".to_string())?;
+        assert_eq!(bunsetsu, vec!["見ていく"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_comma_boundary_more_confident_than_no() -> Result<(), Box<dyn std::error::Error>> {
+        let phrases = split_text_with_confidence("雨の降る日、家にいる".to_string())?;
+        let texts: Vec<&str> = phrases.iter().map(|p| p.text.as_str()).collect();
+        assert_eq!(texts, vec!["雨の", "降る", "日、", "家に", "いる"]);
+
+        let no_boundary = &phrases[0];
+        let comma_boundary = &phrases[2];
+        assert!(comma_boundary.confidence > no_boundary.confidence);
+        assert_eq!(comma_boundary.confidence, 1.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_results_rejoins_seam() -> Result<(), Box<dyn std::error::Error>> {
+        let prev = split_text_into_result("このケーキは美味しい".to_string())?;
+        let appended = split_text_into_result("です".to_string())?;
+        assert_eq!(prev.phrases.last().map(|s| s.as_str()), Some("美味しい"));
+
+        let merged = merge_results(prev, appended)?;
+        assert_eq!(merged.text, "このケーキは美味しいです");
+        assert_eq!(
+            merged.phrases.last().map(|s| s.as_str()),
+            Some("美味しいです")
+        );
+        assert_eq!(
+            merged.phrases,
+            split_text_into_bunsetsu("このケーキは美味しいです".to_string())?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_results_keeps_sentence_boundary() -> Result<(), Box<dyn std::error::Error>> {
+        let prev = split_text_into_result("雨が降った。".to_string())?;
+        let appended = split_text_into_result("です".to_string())?;
+        let prev_len = prev.phrases.len();
+
+        let merged = merge_results(prev, appended)?;
+        assert_eq!(merged.phrases.len(), prev_len + 1);
+        assert_eq!(merged.phrases.last().map(|s| s.as_str()), Some("です"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_count_mora() {
+        assert_eq!(count_mora("キョウ"), 2);
+        assert_eq!(count_mora("ガッコウ"), 4);
+        assert_eq!(count_mora("コーヒー"), 4);
+        assert_eq!(count_mora("シンブン"), 4);
+    }
+
+    #[test]
+    fn test_count_text_mora_haiku() -> Result<(), Box<dyn std::error::Error>> {
+        let lines = ["古池や", "蛙飛び込む", "水の音"];
+        let counts = lines
+            .iter()
+            .map(|line| count_text_mora(line.to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(counts, vec![5, 7, 5]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_word_internal_symbols() -> Result<(), Box<dyn std::error::Error>> {
+        let bunsetsu = split_text_into_bunsetsu("50％増加".to_string())?;
+        assert_eq!(bunsetsu, vec!["50％増加"]);
+
+        let bunsetsu = split_text_into_bunsetsu("Q＆A".to_string())?;
+        assert_eq!(bunsetsu, vec!["Q＆A"]);
+
+        let bunsetsu = split_text_into_bunsetsu("彼はQ＆Aを見た".to_string())?;
+        assert_eq!(bunsetsu, vec!["彼は", "Q＆Aを", "見た"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_annotate_lookup_links() -> Result<(), Box<dyn std::error::Error>> {
+        let html = annotate_text_with_lookup_links("本を読んだ".to_string())?;
+        assert_eq!(
+            html,
+            "<span class=\"lookup\" data-base=\"本\">本</span>を\
+             <span class=\"lookup\" data-base=\"読む\">読ん</span>だ"
+        );
+
+        let html = annotate_text_with_lookup_links("<b>".to_string())?;
+        assert!(!html.contains("<b>"));
+        assert!(html.contains("&lt;"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_bunsetsu_error_variants() -> Result<(), Box<dyn std::error::Error>> {
+        let _lifecycle = lock_tokenizer_lifecycle();
+        assert_eq!(
+            split_text_into_bunsetsu("  \n".to_string()),
+            Err(BunsetsuError::EmptyInput)
+        );
+        assert_eq!(
+            analyze_text(String::new(), None),
+            Err(BunsetsuError::EmptyInput)
+        );
+
+        // 存在しないユーザー辞書は DictionaryLoad になり、読み込み済みのトークナイザはそのまま使える
+        let missing = std::env::temp_dir().join("easy-reading-no-such-userdic.csv");
+        assert!(matches!(
+            create_tokenizer_with_user_dict(&missing),
+            Err(BunsetsuError::DictionaryLoad(_))
+        ));
+        assert_eq!(
+            split_text_into_bunsetsu("本を読む".to_string())?,
+            vec!["本を", "読む"]
+        );
+
+        // フロントエンドには kind と detail で渡る
+        assert_eq!(
+            serde_json::to_value(BunsetsuError::Tokenize("bad".to_string()))?,
+            serde_json::json!({ "kind": "Tokenize", "detail": "bad" })
+        );
+        assert_eq!(
+            serde_json::to_value(BunsetsuError::EmptyInput)?,
+            serde_json::json!({ "kind": "EmptyInput" })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_unavailable_dictionary() {
+        let _lifecycle = lock_tokenizer_lifecycle();
+        assert!(available_dictionary_kinds().contains(&"ipadic".to_string()));
+        assert!(select_dictionary("ipadic").is_ok());
+
+        #[cfg(not(feature = "unidic"))]
+        match select_dictionary("unidic") {
+            Err(DictionaryError::Unavailable {
+                dictionary,
+                feature,
+            }) => {
+                assert_eq!(dictionary, "unidic");
+                assert_eq!(feature, "lindera/unidic");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        assert!(matches!(
+            select_dictionary("no-such-dic"),
+            Err(DictionaryError::Unknown(_))
+        ));
+    }
+
+    #[test]
+    fn test_split_compound_particles_option() -> Result<(), Box<dyn std::error::Error>> {
+        let bunsetsu = split_text_into_bunsetsu("学校では".to_string())?;
+        assert_eq!(bunsetsu, vec!["学校では"]);
+
+        let options = BoundaryOptions {
+            split_compound_particles: true,
+            ..Default::default()
+        };
+        let bunsetsu = split_text_into_bunsetsu_with_options("学校では".to_string(), &options)?;
+        assert_eq!(bunsetsu, vec!["学校で", "は"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_with_trace() -> Result<(), Box<dyn std::error::Error>> {
+        let result = split_text_with_trace("本を読む".to_string(), true)?;
+        assert_eq!(result.phrases, vec!["本を", "読む"]);
+        assert_eq!(result.trace.len(), 2);
+        assert_eq!(result.trace[1]["current"], "を");
+        assert_eq!(result.trace[1]["boundary"], true);
+
+        let result = split_text_with_trace("本を読む".to_string(), false)?;
+        assert!(result.trace.is_empty());
+
+        // 本/を/読む/。/雨/が/降る：文が変わると位置が 0 に戻る
+        let result = split_text_with_trace("本を読む。雨が降る".to_string(), true)?;
+        let indices: Vec<u64> = result
+            .trace
+            .iter()
+            .filter_map(|entry| entry["sentence_token_index"].as_u64())
+            .collect();
+        assert_eq!(indices, [0, 1, 2, 3, 0, 1]);
+        assert_eq!(result.trace[4]["current"], "雨");
+        Ok(())
+    }
+
+    #[test]
+    fn test_volitional_endings_stay_attached() -> Result<(), Box<dyn std::error::Error>> {
+        let bunsetsu = split_text_into_bunsetsu("帰ろう".to_string())?;
+        assert_eq!(bunsetsu, vec!["帰ろう"]);
+
+        let bunsetsu = split_text_into_bunsetsu("始めましょうか".to_string())?;
+        assert_eq!(bunsetsu, vec!["始めましょう", "か"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_counter_reading() -> Result<(), Box<dyn std::error::Error>> {
+        assert_eq!(counter_reading("1", "本").as_deref(), Some("イッポン"));
+        assert_eq!(counter_reading("3", "本").as_deref(), Some("サンボン"));
+        assert_eq!(counter_reading("6", "本").as_deref(), Some("ロッポン"));
+        assert_eq!(counter_reading("２", "本").as_deref(), Some("ニホン"));
+        assert_eq!(counter_reading("10", "杯").as_deref(), Some("ジュッパイ"));
+        assert_eq!(
+            counter_reading("300", "本").as_deref(),
+            Some("サンビャッポン")
+        );
+        assert_eq!(counter_reading("1000", "本").as_deref(), Some("センボン"));
+        assert_eq!(counter_reading("6", "冊").as_deref(), Some("ロクサツ"));
+        assert_eq!(counter_reading("8", "冊").as_deref(), Some("ハッサツ"));
+        assert_eq!(counter_reading("3", "人"), None);
+
+        let ssml = text_to_ssml("鉛筆を3本買う".to_string(), &SsmlOptions::default())?;
+        assert!(
+            ssml.contains("<sub alias=\"サンボン\">3本</sub>"),
+            "{}",
+            ssml
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_text_to_ssml() -> Result<(), Box<dyn std::error::Error>> {
+        // 辞書による読みや区切りの違いに左右されない性質だけを確かめる
+        let text = "本を読む。寝る";
+        let ssml = text_to_ssml(text.to_string(), &SsmlOptions::default())?;
+        assert!(
+            ssml.starts_with("<speak>") && ssml.ends_with("</speak>"),
+            "{}",
+            ssml
+        );
+        // タグを除くと元のテキストに戻る
+        let mut inside_tag = false;
+        let spoken: String = ssml
+            .chars()
+            .filter(|c| match c {
+                '<' => {
+                    inside_tag = true;
+                    false
+                }
+                '>' => {
+                    inside_tag = false;
+                    false
+                }
+                _ => !inside_tag,
+            })
+            .collect();
+        assert_eq!(spoken, text);
+        // 文の区切りは句点の直後に一度だけ入り、文節の区切りも入る。末尾には区切りを入れない
+        assert_eq!(ssml.matches("<break time=\"400ms\"/>").count(), 1);
+        assert!(ssml.contains("。<break time=\"400ms\"/>"), "{}", ssml);
+        assert!(ssml.contains("<break time=\"100ms\"/>"), "{}", ssml);
+        assert!(!ssml.contains("/></speak>"), "{}", ssml);
+        assert!(ssml.contains("<sub alias=\""), "{}", ssml);
+
+        let options = SsmlOptions {
+            phrase_break_ms: 50,
+            sentence_break_ms: 800,
+        };
+        let ssml = text_to_ssml("本を読む。寝る".to_string(), &options)?;
+        assert!(ssml.contains("<break time=\"50ms\"/>"));
+        assert!(ssml.contains("<break time=\"800ms\"/>"));
+        assert_eq!(
+            ssml.matches("<sub ").count(),
+            ssml.matches("</sub>").count()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_light_verb_suru() -> Result<(), Box<dyn std::error::Error>> {
+        let bunsetsu = split_text_into_bunsetsu("明日は勉強しない".to_string())?;
+        assert_eq!(bunsetsu, vec!["明日は", "勉強しない"]);
+
+        let bunsetsu = split_text_into_bunsetsu("図書館で勉強します".to_string())?;
+        assert_eq!(bunsetsu, vec!["図書館で", "勉強します"]);
+
+        let bunsetsu = split_text_into_bunsetsu("家でゆっくりする".to_string())?;
+        assert_eq!(bunsetsu, vec!["家で", "ゆっくりする"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_formal_copula_dearu() -> Result<(), Box<dyn std::error::Error>> {
+        for text in ["学生であり", "重要であれば", "学生であって", "学生であった"]
+        {
+            assert_eq!(split_text_into_bunsetsu(text.to_string())?, vec![text]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_tari_enumeration() -> Result<(), Box<dyn std::error::Error>> {
+        let bunsetsu = split_text_into_bunsetsu("読んだり書いたりする".to_string())?;
+        assert_eq!(bunsetsu, vec!["読んだり", "書いたりする"]);
+
+        let bunsetsu = split_text_into_bunsetsu("食べたり飲んだり".to_string())?;
+        assert_eq!(bunsetsu, vec!["食べたり", "飲んだり"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_romaji_of_text() -> Result<(), Box<dyn std::error::Error>> {
+        let style = RomajiStyle::default();
+        assert_eq!(romaji_of_text("学校".to_string(), style)?, "gakkō");
+        assert_eq!(romaji_of_text("切符".to_string(), style)?, "kippu");
+        assert_eq!(romaji_of_text("new york".to_string(), style)?, "new york");
+        assert_eq!(
+            romaji_of_text("学校".to_string(), RomajiStyle::Wapuro)?,
+            "gakkou"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_analyze_text_pos_filter() -> Result<(), Box<dyn std::error::Error>> {
+        let filter = vec!["名詞".to_string()];
+        let words = analyze_text("東京で本を読む。".to_string(), Some(&filter))?;
+        let texts: Vec<&str> = words.iter().map(|w| w.text.as_str()).collect();
+        assert_eq!(texts, vec!["東京", "本"]);
+        assert!(words.iter().all(|w| w.pos == "名詞"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_analyze_text_readings() -> Result<(), Box<dyn std::error::Error>> {
+        let words = analyze_text("東京都に住む。".to_string(), None)?;
+        let readings: Vec<Option<&str>> = words.iter().map(|w| w.reading.as_deref()).collect();
+        assert_eq!(
+            readings,
+            [
+                Some("とうきょう"),
+                Some("と"),
+                Some("に"),
+                Some("すむ"),
+                None
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_potential_forms_chunk_together() -> Result<(), Box<dyn std::error::Error>> {
+        assert_eq!(
+            split_text_into_bunsetsu("食べられる".to_string())?,
+            vec!["食べられる"]
+        );
+        assert_eq!(
+            split_text_into_bunsetsu("食べれる".to_string())?,
+            vec!["食べれる"]
+        );
+
+        assert_eq!(
+            ra_nuki_forms_in_text("食べれる".to_string())?,
+            vec!["食べれる"]
+        );
+        assert!(ra_nuki_forms_in_text("食べられる".to_string())?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_pos_transitions_sum() -> Result<(), Box<dyn std::error::Error>> {
+        let text = "人間は文章を読む時、滑らかに文字を読んでいる。";
+        let matrix = pos_transitions_in_text(text.to_string())?;
+        let total: usize = matrix.values().flat_map(|row| row.values()).sum();
+        assert_eq!(total, tokenize_to_infos(text)?.len() - 1);
+        assert_eq!(matrix["名詞"]["助詞"], 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_detailed() -> Result<(), Box<dyn std::error::Error>> {
+        let bunsetsu = split_text_into_detailed("赤い花が咲いた".to_string())?;
+        let summary: Vec<(&str, Vec<&str>, &str)> = bunsetsu
+            .iter()
+            .map(|b| {
+                (
+                    b.surface.as_str(),
+                    b.tokens.iter().map(|t| t.text.as_str()).collect(),
+                    b.head_pos.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("赤い", vec!["赤い"], "形容詞"),
+                ("花が", vec!["花", "が"], "名詞"),
+                ("咲いた", vec!["咲い", "た"], "動詞"),
+            ]
+        );
+        assert_eq!(bunsetsu[1].tokens[1].pos, "助詞");
+        Ok(())
+    }
+
+    #[test]
+    fn test_nagara_attaches_to_verb() -> Result<(), Box<dyn std::error::Error>> {
+        assert_eq!(
+            split_text_into_bunsetsu("歩きながら".to_string())?,
+            vec!["歩きながら"]
+        );
+        assert_eq!(
+            split_text_into_bunsetsu("歩きながら話す".to_string())?,
+            vec!["歩きながら", "話す"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_colloquial_quotative_tte() -> Result<(), Box<dyn std::error::Error>> {
+        assert_eq!(
+            split_text_into_bunsetsu("無理だって言った".to_string())?,
+            vec!["無理だって言った"]
+        );
+        assert_eq!(
+            split_text_into_bunsetsu("無理だと言った".to_string())?,
+            vec!["無理だと言った"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_estimate_reading_time() -> Result<(), Box<dyn std::error::Error>> {
+        let kanji = estimate_text_reading_time("経済政策会議開催".to_string(), 400.0)?;
+        let kana = estimate_text_reading_time("きょうはあめです".to_string(), 400.0)?;
+        assert_eq!(kanji.char_count, kana.char_count);
+        assert!(kanji.seconds > kana.seconds);
+        assert!((kana.seconds - 8.0 / 400.0 * 60.0).abs() < 1e-9);
+
+        assert!(matches!(
+            estimate_text_reading_time("本".to_string(), 0.0),
+            Err(BunsetsuError::InvalidArgument(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_wo_ends_phrase() -> Result<(), Box<dyn std::error::Error>> {
+        assert_eq!(
+            split_text_into_bunsetsu("勉強をする".to_string())?,
+            vec!["勉強を", "する"]
+        );
+        assert_eq!(
+            split_text_into_bunsetsu("気をつける".to_string())?,
+            vec!["気を", "つける"]
+        );
+
+        // 後ろに助詞が続く場合を除き、「を」の後ろは必ず区切る
+        let options = BoundaryOptions::default();
+        for text in [
+            "本を読む",
+            "食事をしている",
+            "注意を払って歩く",
+            "命をも惜しまない",
+        ] {
+            let token_infos = tokenize_to_infos(text)?;
+            let boundaries = compute_boundaries(&token_infos, &options);
+            for (i, pair) in token_infos.windows(2).enumerate() {
+                if pair[0].text == "を" && pair[0].pos() == "助詞" {
+                    assert_eq!(boundaries[i], pair[1].pos() != "助詞", "{}", text);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_boundary_vector_snapshot() -> Result<(), Box<dyn std::error::Error>> {
+        // 人間/は/文章/を/読む/時/、/滑らか/に/文字/を/読ん/で/いる/。
+        let boundaries =
+            boundary_vector("人間は文章を読む時、滑らかに文字を読んでいる。".to_string())?;
+        assert_eq!(
+            boundaries,
+            vec![
+                false, true, false, true, true, false, true, false, true, false, true, false,
+                false, true
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_numeric_expressions() -> Result<(), Box<dyn std::error::Error>> {
+        let bunsetsu = split_text_into_bunsetsu("3〜5人".to_string())?;
+        assert_eq!(bunsetsu, vec!["3〜5人"]);
+
+        let bunsetsu = split_text_into_bunsetsu("2/3".to_string())?;
+        assert_eq!(bunsetsu, vec!["2/3"]);
+
+        let bunsetsu = split_text_into_bunsetsu("10:30から始まる".to_string())?;
+        assert_eq!(bunsetsu, vec!["10:30から", "始まる"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_homophone_groups() -> Result<(), Box<dyn std::error::Error>> {
+        let groups = homophone_groups_in_text("橋の上で箸を使う。橋は長い".to_string())?;
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups["ハシ"], vec!["橋", "箸"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_expand_contractions() -> Result<(), Box<dyn std::error::Error>> {
+        assert_eq!(expand_contractions("食べてる")?, "食べている");
+        assert_eq!(expand_contractions("食べちゃう")?, "食べてしまう");
+        assert_eq!(expand_contractions("行かなきゃ")?, "行かなければ");
+        assert_eq!(expand_contractions("わかんない")?, "わからない");
+        // 縮約形でない語はそのまま
+        assert_eq!(expand_contractions("ごみを捨てる")?, "ごみを捨てる");
+
+        let options = BoundaryOptions {
+            expand_contractions: true,
+            ..Default::default()
+        };
+        let bunsetsu = split_text_into_bunsetsu_with_options("食べてる".to_string(), &options)?;
+        assert_eq!(
+            bunsetsu,
+            split_text_into_bunsetsu("食べている".to_string())?
+        );
+        assert_eq!(bunsetsu, vec!["食べている"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_truncate_preview() -> Result<(), Box<dyn std::error::Error>> {
+        assert_eq!(truncate_preview_text("本を読む".to_string(), 3)?, "本を…");
+        assert_eq!(
+            truncate_preview_text("本を読む".to_string(), 4)?,
+            "本を読む"
+        );
+
+        // 結合文字の濁点（か + ゛）を含む文字が境界をまたぐ場合
+        let text = "か\u{3099}か\u{3099}か\u{3099}";
+        let preview = truncate_preview_text(text.to_string(), 2)?;
+        let kept = preview.strip_suffix('…').unwrap_or_default();
+        let graphemes: Vec<&str> = kept.graphemes(true).collect();
+        assert!((1..=2).contains(&graphemes.len()), "{}", preview);
+        assert!(graphemes.iter().all(|g| *g == "か\u{3099}"), "{}", preview);
+
+        // 正規化を有効にしても、プレビューは入力の表記のまま
+        let _rules = override_boundary_rules(BoundaryOptions {
+            normalize: Some(NormalizeOptions::default()),
+            expand_contractions: true,
+            ..BoundaryOptions::default()
+        });
+        let text = "ＡＢＣを見てる";
+        assert_eq!(truncate_preview_text(text.to_string(), 20)?, text);
+        let preview = truncate_preview_text(text.to_string(), 4)?;
+        assert!(preview.starts_with("ＡＢＣ"), "{}", preview);
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_into_sentences() -> Result<(), Box<dyn std::error::Error>> {
+        assert_eq!(
+            split_text_into_sentences("今日は晴れ。明日は雨。".to_string()),
+            vec!["今日は晴れ。", "明日は雨。"]
+        );
+        // 括弧の中の「。」では区切らない
+        assert_eq!(
+            split_text_into_sentences("彼は「そうだ。行こう。」と言った。本当？！".to_string()),
+            vec!["彼は「そうだ。行こう。」と言った。", "本当？！"]
+        );
+        // 半角の！？でも区切り、文の途中の「…」では区切らない
+        assert_eq!(
+            split_text_into_sentences("えっと…それは本だ。Really?はい!そうか…。".to_string()),
+            vec!["えっと…それは本だ。", "Really?", "はい!", "そうか…。"]
+        );
+        assert!(ends_with_sentence_boundary("はい!"));
+        assert!(!ends_with_sentence_boundary("えっと…"));
+
+        let options = BoundaryOptions {
+            split_by_sentence: true,
+            ..Default::default()
+        };
+        let phrases =
+            split_text_into_bunsetsu_with_options("今日は晴れ。明日は雨。".to_string(), &options)?;
+        assert_eq!(phrases.concat(), "今日は晴れ。明日は雨。");
+        // 文末記号は文節の途中に入らない
+        assert!(phrases
+            .iter()
+            .all(|phrase| !phrase.trim_end_matches('。').contains('。')));
+        Ok(())
+    }
+
+    #[test]
+    fn test_compute_readability() -> Result<(), Box<dyn std::error::Error>> {
+        assert_eq!(compute_readability(String::new())?, Readability::default());
+
+        // 文末記号がなければ一文
+        let readability = compute_readability("本を読む".to_string())?;
+        assert_eq!(readability.sentence_count, 1);
+        assert_eq!(readability.average_bunsetsu_per_sentence, 2.0);
+
+        let easy = compute_readability("ねこがいる。いぬもいる。".to_string())?;
+        let hard = compute_readability("経済政策会議開催予定。".to_string())?;
+        assert_eq!(easy.sentence_count, 2);
+        assert_eq!(easy.kanji_ratio, 0.0);
+        assert!(easy.ease > hard.ease);
+        assert!((0.0..=100.0).contains(&hard.ease));
+        Ok(())
+    }
+
+    #[test]
+    fn test_analyze_sentences() -> Result<(), Box<dyn std::error::Error>> {
+        let sentences =
+            analyze_sentences_in_text("本を読む。経済政策を議論した。".to_string(), 500.0)?;
+        assert_eq!(sentences.len(), 2);
+        assert_eq!(sentences[0].text, "本を読む。");
+        assert_eq!(sentences[1].text, "経済政策を議論した。");
+        for sentence in &sentences {
+            assert!(sentence.difficulty > 0.0);
+            assert!(sentence.reading_seconds > 0.0);
+            assert!(sentence.phrase_count >= 2);
+        }
+        assert!(sentences[1].difficulty > sentences[0].difficulty);
+        Ok(())
+    }
+
+    #[test]
+    fn test_token_difficulties() -> Result<(), Box<dyn std::error::Error>> {
+        let difficulties = token_difficulties_in_text("食べ物とりんご".to_string())?;
+        let level_of = |surface: &str| {
+            difficulties
+                .iter()
+                .find(|d| d.surface == surface)
+                .and_then(|d| d.level)
+        };
+        // 食（2 年）と物（3 年）のうち難しい方
+        assert_eq!(level_of("食べ物"), Some(KanjiLevel::Grade(3)));
+        assert_eq!(level_of("りんご"), None);
+
+        // サロゲートペアの漢字（𠮷）を含んでも文字単位で扱える
+        let difficulties = token_difficulties_in_text("𠮷野家".to_string())?;
+        assert_eq!(
+            difficulties.iter().filter_map(|d| d.level).max(),
+            Some(KanjiLevel::Hyogai)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_phrase_scripts() -> Result<(), Box<dyn std::error::Error>> {
+        assert_eq!(classify_phrase_script("東京タワー"), "mixed");
+        assert_eq!(classify_phrase_script("こんにちは"), "kana");
+        assert_eq!(classify_phrase_script("東京都"), "kanji");
+        assert_eq!(classify_phrase_script("Rust"), "latin");
+        assert_eq!(classify_phrase_script("。"), "other");
+
+        let scripts = phrase_scripts_in_text("こんにちは".to_string())?;
+        assert_eq!(
+            scripts,
+            vec![PhraseScript {
+                text: "こんにちは".to_string(),
+                script: "kana".to_string(),
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_ruby_segments() -> Result<(), Box<dyn std::error::Error>> {
+        let ruby = |surface: &str, reading: Option<&str>| RubySegment {
+            surface: surface.to_string(),
+            reading: reading.map(str::to_string),
+        };
+        assert_eq!(
+            ruby_segments_in_text("食べる".to_string())?,
+            vec![ruby("食", Some("た")), ruby("べる", None)]
+        );
+        assert_eq!(
+            ruby_segments_in_text("お茶".to_string())?,
+            vec![ruby("お", None), ruby("茶", Some("ちゃ"))]
+        );
+        let segments = ruby_segments_in_text("走って。".to_string())?;
+        assert_eq!(segments.first(), Some(&ruby("走", Some("はし"))));
+        assert!(segments[1..]
+            .iter()
+            .all(|segment| segment.reading.is_none()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_ruby_annotation_overflow() -> Result<(), Box<dyn std::error::Error>> {
+        let annotations = ruby_annotations_in_text("承る".to_string(), DEFAULT_MAX_RUBY_PER_BASE)?;
+        let first = &annotations[0];
+        assert_eq!(first.surface, "承");
+        assert_eq!(first.reading.as_deref(), Some("うけたまわ"));
+        assert_eq!((first.base_chars, first.ruby_chars), (1, 5));
+        assert!(first.overflows);
+        assert!(!annotations[1].overflows);
+
+        let annotations =
+            ruby_annotations_in_text("食べる".to_string(), DEFAULT_MAX_RUBY_PER_BASE)?;
+        assert!(annotations.iter().all(|annotation| !annotation.overflows));
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_aozora_ruby() -> Result<(), Box<dyn std::error::Error>> {
+        assert_eq!(
+            to_aozora_ruby("食べ物".to_string())?,
+            "｜食《た》べ｜物《もの》"
+        );
+        // 振り仮名の不要な語はそのまま
+        assert_eq!(to_aozora_ruby("りんご".to_string())?, "りんご");
+        Ok(())
+    }
+
+    #[test]
+    fn test_sentence_has_predicate() -> Result<(), Box<dyn std::error::Error>> {
+        let sentences = analyze_sentence_structure("青い空と白い雲。私は海へ行った。".to_string())?;
+        assert_eq!(sentences.len(), 2);
+        assert_eq!(sentences[0].text, "青い空と白い雲。");
+        // 名詞だけで終わる文（体言止め）
+        assert!(!sentences[0].has_predicate);
+        assert!(sentences[1].has_predicate);
+
+        let sentences = analyze_sentence_structure("空が青いね".to_string())?;
+        assert!(sentences[0].has_predicate);
+        Ok(())
+    }
+
+    #[test]
+    fn test_word_frequencies_normalize_variants() -> Result<(), Box<dyn std::error::Error>> {
+        let text = "子供と子どもが遊ぶ".to_string();
+
+        let frequencies = word_frequencies_in_text(text.clone(), &FrequencyOptions::default())?;
+        assert_eq!(frequencies.get("子供"), Some(&1));
+
+        let options = FrequencyOptions {
+            normalize_variants: true,
+            ..Default::default()
+        };
+        let frequencies = word_frequencies_in_text(text, &options)?;
+        assert_eq!(frequencies.get("子供"), Some(&2));
+        assert_eq!(frequencies.get("遊ぶ"), Some(&1));
+
+        // 送り仮名のゆれも、解析したトークンの並びでまとめる
+        let frequencies = word_frequencies_in_text("受付けと受け付けを見た".to_string(), &options)?;
+        assert_eq!(frequencies.get("受け付け"), Some(&2), "{:?}", frequencies);
+
+        // 語をまたぐ部分（ねこ＋ども）は表記ゆれとして扱わない
+        let frequencies = word_frequencies_in_text("ねこどもが来た".to_string(), &options)?;
+        assert_eq!(frequencies.get("子供"), None, "{:?}", frequencies);
+        Ok(())
+    }
+
+    #[test]
+    fn test_furigana_spans_in_range() -> Result<(), Box<dyn std::error::Error>> {
+        // 本/を/読む/。/水/を/飲む/。
+        let text = "本を読む。水を飲む。".to_string();
+
+        let spans = furigana_spans_in_range(text.clone(), 5, 7)?;
+        assert_eq!(
+            spans,
+            vec![FuriganaSpan {
+                start: 5,
+                end: 6,
+                text: "水".to_string(),
+                reading: "みず".to_string(),
+            }]
+        );
+
+        // 語の途中から始まる範囲はその語全体に広げる
+        let spans = furigana_spans_in_range(text, 3, 4)?;
+        assert_eq!(spans.len(), 1);
+        assert_eq!((spans[0].start, spans[0].end), (2, 4));
+        assert_eq!(spans[0].reading, "よむ");
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_for_print() -> Result<(), Box<dyn std::error::Error>> {
+        let text = "今日は良い天気です。公園へ行きます。";
+        let options = PrintOptions {
+            line_width: 10,
+            phrase_separator: " ".to_string(),
+            furigana_min_grade: None,
+        };
+        let printed = format_text_for_print(text.to_string(), &options)?;
+        assert!(printed.lines().count() > 1);
+        assert!(printed.lines().all(|line| line.chars().count() <= 10));
+        assert!(printed.lines().any(|line| line.contains(' ')));
+        assert!(printed
+            .lines()
+            .all(|line| !line.starts_with(' ') && !line.ends_with(' ')));
+        assert_eq!(printed.replace([' ', '\n'], ""), text);
+
+        // 2 年生以降に習う漢字にだけ読みを付ける（先・生は 1 年生）
+        let print_with = |min_grade: u8| {
+            let options = PrintOptions {
+                line_width: 0,
+                phrase_separator: "　".to_string(),
+                furigana_min_grade: Some(min_grade),
+            };
+            format_text_for_print("先生に質問する".to_string(), &options)
+        };
+        assert_eq!(print_with(2)?, "先生に　質問（しつもん）する");
+        // 質は 5 年生の漢字。5 年生以降なら付け、6 年生以降なら付けない
+        assert_eq!(print_with(5)?, "先生に　質問（しつもん）する");
+        assert_eq!(print_with(6)?, "先生に　質問する");
+        Ok(())
+    }
+
+    #[test]
+    fn test_ruby_html() -> Result<(), Box<dyn std::error::Error>> {
+        // 山は 1 年生の漢字、薔薇は常用外
+        let html = to_ruby_html("山に薔薇が咲く".to_string(), 2)?;
+        assert!(html.starts_with("山に"));
+        assert!(html.contains("<ruby>薔薇<rt>ばら</rt></ruby>"));
+
+        // 質は 5 年生の漢字。印刷用の読みと同じく min_grade の学年を含める
+        assert!(to_ruby_html("質問".to_string(), 5)?.contains("<ruby>"));
+        assert!(!to_ruby_html("質問".to_string(), 6)?.contains("<ruby>"));
+
+        // 特殊文字を含まないテキストは変えず、特殊文字は一度だけエスケープする
+        assert_eq!(to_ruby_html("山と川".to_string(), 2)?, "山と川");
+        let html = to_ruby_html("A&B<山>".to_string(), 2)?;
+        assert!(html.contains("&amp;") && html.contains("&lt;"));
+        assert!(!html.contains("&amp;amp;") && !html.contains("&amp;lt;"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_quantities_stay_whole() -> Result<(), Box<dyn std::error::Error>> {
+        for text in ["12時30分", "三冊", "1,000円", "千二百三十円", "3時間"] {
+            assert_eq!(split_text_into_bunsetsu(text.to_string())?, vec![text]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_comparative_yori() -> Result<(), Box<dyn std::error::Error>> {
+        assert_eq!(
+            split_text_into_bunsetsu("君より速い".to_string())?,
+            vec!["君より", "速い"]
+        );
+        assert_eq!(
+            split_text_into_bunsetsu("君よりも速い".to_string())?,
+            vec!["君よりも", "速い"]
+        );
+
+        let options = BoundaryOptions {
+            split_compound_particles: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            split_text_into_bunsetsu_with_options("君よりも速い".to_string(), &options)?,
+            vec!["君よりも", "速い"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_compound_verbs() -> Result<(), Box<dyn std::error::Error>> {
+        for text in ["読み始める", "走り出す", "考え込む", "食べ始める"] {
+            assert_eq!(split_text_into_bunsetsu(text.to_string())?, vec![text]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_negative_te_form() -> Result<(), Box<dyn std::error::Error>> {
+        assert_eq!(
+            split_text_into_bunsetsu("食べなくて".to_string())?,
+            vec!["食べなくて"]
+        );
+        assert_eq!(
+            split_text_into_bunsetsu("行かないで".to_string())?,
+            vec!["行かないで"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_negative_question_invitation() -> Result<(), Box<dyn std::error::Error>> {
+        // 一緒/に/行き/ませ/ん/か
+        let bunsetsu = split_text_into_bunsetsu("一緒に行きませんか".to_string())?;
+        assert_eq!(bunsetsu, vec!["一緒に", "行きません", "か"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_demonstratives_option() -> Result<(), Box<dyn std::error::Error>> {
+        let bunsetsu = split_text_into_bunsetsu("あの大きな家".to_string())?;
+        assert_eq!(bunsetsu, vec!["あの大きな家"]);
+
+        let options = BoundaryOptions {
+            split_demonstratives: true,
+            ..Default::default()
+        };
+        let bunsetsu = split_text_into_bunsetsu_with_options("あの大きな家".to_string(), &options)?;
+        assert_eq!(bunsetsu, vec!["あの", "大きな家"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_phrase_heads() -> Result<(), Box<dyn std::error::Error>> {
+        let heads = phrase_heads_in_text("美味しいケーキを".to_string())?;
+        let phrases: Vec<&str> = heads.iter().map(|h| h.phrase.as_str()).collect();
+        assert_eq!(phrases, vec!["美味しい", "ケーキを"]);
+        assert_eq!(heads[0].base_form, "美味しい");
+        assert_eq!(heads[1].surface, "ケーキ");
+        assert_eq!(heads[1].base_form, "ケーキ");
+        Ok(())
+    }
+
+    #[test]
+    fn test_interjection_keeps_comma() -> Result<(), Box<dyn std::error::Error>> {
+        let bunsetsu = split_text_into_bunsetsu("はい、そうです".to_string())?;
+        assert_eq!(bunsetsu[0], "はい、");
+        assert!(!bunsetsu.iter().any(|phrase| phrase.starts_with('、')));
+
+        let bunsetsu = split_text_into_bunsetsu("しかし、雨が降った".to_string())?;
+        assert_eq!(bunsetsu[0], "しかし、");
+        Ok(())
+    }
+
+    #[test]
+    fn test_unload_tokenizer_reinitializes() -> Result<(), Box<dyn std::error::Error>> {
+        let _lifecycle = lock_tokenizer_lifecycle();
+        let before = split_text_into_bunsetsu("本を読む".to_string())?;
+
+        unload_tokenizer()?;
+        let after = split_text_into_bunsetsu("本を読む".to_string())?;
+        assert_eq!(before, after);
+        assert!(TOKENIZER.read().map_err(|e| e.to_string())?.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_evaluate_segmentation() -> Result<(), Box<dyn std::error::Error>> {
+        // 分割結果は 雨の/降る/日、/家に/いる（境界 2, 4, 6, 8）
+        let gold = ["雨の降る日、", "家に", "いる"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        let score = score_segmentation("雨の降る日、家にいる".to_string(), gold)?;
+        assert_eq!(score.precision, 0.5);
+        assert_eq!(score.recall, 1.0);
+        assert!((score.f1 - 2.0 / 3.0).abs() < 1e-9);
+
+        let gold = vec!["雨の".to_string()];
+        assert!(matches!(
+            score_segmentation("雨が".to_string(), gold),
+            Err(BunsetsuError::InvalidArgument(_))
+        ));
+
+        // 正規化で文字数が変わっても（ｶﾞ → ガ）、予測の位置は入力テキストで数える
+        let text = "ｶﾞｽを止める";
+        let gold: Vec<String> = vec!["ｶﾞｽを".to_string(), "止める".to_string()];
+        let unnormalized = score_segmentation(text.to_string(), gold.clone())?;
+        let _rules = override_boundary_rules(BoundaryOptions {
+            normalize: Some(NormalizeOptions::default()),
+            ..BoundaryOptions::default()
+        });
+        let normalized = score_segmentation(text.to_string(), gold)?;
+        assert_eq!(normalized.precision, unnormalized.precision);
+        assert_eq!(normalized.recall, unnormalized.recall);
+        Ok(())
+    }
+
+    #[test]
+    fn test_acronym_readings() -> Result<(), Box<dyn std::error::Error>> {
+        load_acronym_readings(default_acronym_readings())?;
+
+        let spans = furigana_spans_in_range("NHKを見る".to_string(), 0, 3)?;
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].text, "NHK");
+        assert_eq!(spans[0].reading, "エヌエイチケー");
+
+        let ssml = text_to_ssml("NHKを見る".to_string(), &SsmlOptions::default())?;
+        assert!(ssml.contains("<sub alias=\"エヌエイチケー\">NHK</sub>"));
+
+        // 表にない略語には読みを付けない
+        assert_eq!(acronym_reading("XYZ"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_conjunctive_particle_keeps_final_particle() -> Result<(), Box<dyn std::error::Error>> {
+        let bunsetsu = split_text_into_bunsetsu("いいけどね".to_string())?;
+        assert_eq!(bunsetsu, vec!["いいけどね"]);
+
+        let options = BoundaryOptions {
+            split_compound_particles: true,
+            ..Default::default()
+        };
+        let bunsetsu = split_text_into_bunsetsu_with_options("いいけどね".to_string(), &options)?;
+        assert_eq!(bunsetsu, vec!["いいけどね"]);
+
+        let bunsetsu = split_text_into_bunsetsu("暑いのに出かけた".to_string())?;
+        assert_eq!(bunsetsu, vec!["暑いのに", "出かけた"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_iob_tags() -> Result<(), Box<dyn std::error::Error>> {
+        // 本/を/読む
+        let tagged = iob_tags_in_text("本を読む".to_string())?;
+        let tags: Vec<&str> = tagged.iter().map(|t| t.tag.as_str()).collect();
+        assert_eq!(tags, vec!["B", "I", "B"]);
+        assert_eq!(tagged[2].text, "読む");
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_dependencies() -> Result<(), Box<dyn std::error::Error>> {
+        let heads = |text: &str| -> Result<Vec<(String, Option<usize>)>, BunsetsuError> {
+            Ok(parse_dependencies_in_text(text.to_string())?
+                .into_iter()
+                .map(|dep| (dep.surface, dep.head))
+                .collect())
+        };
+
+        assert_eq!(
+            heads("赤い花が咲いた")?,
+            vec![
+                ("赤い".to_string(), Some(1)),
+                ("花が".to_string(), Some(2)),
+                ("咲いた".to_string(), None),
+            ]
+        );
+
+        // 格助詞の文節は間の文節を飛ばして述語に係る
+        let deps = parse_dependencies_in_text("本を図書館で読む".to_string())?;
+        assert_eq!(deps[0].head, Some(2));
+        assert_eq!(deps[1].head, Some(2));
+
+        // 文をまたいで係らない
+        let deps = parse_dependencies_in_text("花が咲いた。鳥が鳴く".to_string())?;
+        let first_sentence_end = deps
+            .iter()
+            .position(|dep| dep.surface.ends_with('。'))
+            .unwrap_or(deps.len());
+        assert!(deps[..first_sentence_end]
+            .iter()
+            .all(|dep| !matches!(dep.head, Some(head) if head > first_sentence_end)));
+        assert_eq!(deps.last().and_then(|dep| dep.head), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_paragraphs() -> Result<(), Box<dyn std::error::Error>> {
+        let text = "今日は晴れ。\n\n　明日は雨。\n";
+        let paragraphs = split_text_into_paragraphs(text.to_string())?;
+        assert_eq!(paragraphs.len(), 2);
+        assert_eq!(paragraphs[0].last().map(String::as_str), Some("\n\n"));
+        assert_eq!(paragraphs[1].first().map(String::as_str), Some("　"));
+        assert_eq!(paragraphs[1].last().map(String::as_str), Some("\n"));
+        assert_eq!(paragraphs.concat().concat(), text);
+        Ok(())
+    }
+
+    #[test]
+    fn test_normalize_text() -> Result<(), Box<dyn std::error::Error>> {
+        let all = NormalizeOptions::default();
+        assert_eq!(normalize_text("ＡＢＣ１２３", all), "ABC123");
+        assert_eq!(normalize_text("ｶﾞｷﾞﾊﾟﾝｳﾞ", all), "ガギパンヴ");
+        assert_eq!(normalize_text("ｶﾀｶﾅ｡", all), "カタカナ。");
+        assert_eq!(normalize_text("3～5", all), normalize_text("3〜5", all));
+        assert_eq!(normalize_text("3～5", all), "3〜5");
+
+        // 指定しない変換は行わない
+        let katakana_only = NormalizeOptions {
+            fold_width: false,
+            unify_wave_dash: false,
+            ..Default::default()
+        };
+        assert_eq!(normalize_text("ＡＢＣｶﾞ～", katakana_only), "ＡＢＣガ～");
+
+        let options = BoundaryOptions {
+            normalize: Some(all),
+            ..Default::default()
+        };
+        assert_eq!(
+            split_text_into_bunsetsu_with_options("ﾎﾟｹｯﾄを探す".to_string(), &options)?,
+            vec!["ポケットを", "探す"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_simple_split_strategy() -> Result<(), Box<dyn std::error::Error>> {
+        let simple = BoundaryOptions {
+            strategy: SplitStrategy::Simple,
+            ..Default::default()
+        };
+        let text = "とても美しい花を見た";
+
+        // 規則による判定では副詞・連体修飾の後ろで区切るが、簡易な判定では助詞が付くまで区切らない
+        assert_eq!(
+            split_text_into_bunsetsu_with_options(text.to_string(), &BoundaryOptions::default())?,
+            vec!["とても", "美しい", "花を", "見た"]
+        );
+        assert_eq!(
+            split_text_into_bunsetsu_with_options(text.to_string(), &simple)?,
+            vec!["とても美しい花を", "見た"]
+        );
+
+        // 助詞の付かない長い複合語は 15 文字で区切る
+        let text = "国際連合安全保障理事会常任理事国";
+        let phrases = split_text_into_bunsetsu_with_options(text.to_string(), &simple)?;
+        assert!(phrases.len() > 1);
+        assert!(phrases
+            .iter()
+            .all(|phrase| phrase.chars().count() <= SIMPLE_MAX_PHRASE_CHARS));
+        assert_eq!(phrases.concat(), text);
+        Ok(())
+    }
+
+    #[test]
+    fn test_auxiliary_chain_limit_option() -> Result<(), Box<dyn std::error::Error>> {
+        // 食べ/させ/られ/たく/なかっ/た
+        let bunsetsu = split_text_into_bunsetsu("食べさせられたくなかった".to_string())?;
+        assert_eq!(bunsetsu, vec!["食べさせられたくなかった"]);
+
+        let options = BoundaryOptions {
+            auxiliary_chain_limit: Some(2),
+            ..Default::default()
+        };
+        let bunsetsu = split_text_into_bunsetsu_with_options(
+            "食べさせられたくなかった".to_string(),
+            &options,
+        )?;
+        assert_eq!(bunsetsu, vec!["食べさせられ", "たくなかった"]);
+
+        // 短い連なりは区切らない
+        let bunsetsu = split_text_into_bunsetsu_with_options("食べた".to_string(), &options)?;
+        assert_eq!(bunsetsu, vec!["食べた"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_vocabulary_collapses_inflections() -> Result<(), Box<dyn std::error::Error>> {
+        let text = "朝はパンを食べた。昼もパンを食べる。夜はご飯を食べて寝た。";
+        let vocabulary = vocabulary_in_text(text.to_string(), &VocabOptions::default())?;
+
+        let taberu: Vec<&VocabEntry> = vocabulary
+            .iter()
+            .filter(|entry| entry.base_form == "食べる")
+            .collect();
+        assert_eq!(taberu.len(), 1);
+        assert_eq!(taberu[0].count, 3);
+        assert_eq!(taberu[0].reading.as_deref(), Some("たべる"));
+        assert_eq!(vocabulary[0].base_form, "食べる");
+
+        // 助詞・助動詞・記号は既定では含めない
+        assert!(vocabulary
+            .iter()
+            .all(|entry| !matches!(entry.pos.as_str(), "助詞" | "助動詞" | "記号")));
+        let options = VocabOptions {
+            include_function_words: true,
+        };
+        let vocabulary = vocabulary_in_text(text.to_string(), &options)?;
+        assert!(vocabulary.iter().any(|entry| entry.base_form == "を"));
+
+        // 出現回数の多い順、同じなら読みの順
+        assert!(vocabulary
+            .windows(2)
+            .all(|pair| pair[0].count >= pair[1].count));
+        Ok(())
+    }
+
+    #[test]
+    fn test_anki_tsv() -> Result<(), Box<dyn std::error::Error>> {
+        let text = "朝はパンを食べた。\n夜もパンを\n食べる。";
+        let tsv = anki_tsv_of_text(text.to_string())?;
+        let vocabulary = vocabulary_in_text(text.to_string(), &VocabOptions::default())?;
+
+        let rows: Vec<&str> = tsv.lines().collect();
+        assert_eq!(rows.len(), vocabulary.len());
+        assert!(rows.iter().all(|row| row.split('\t').count() == 4));
+        assert!(rows.contains(&"食べる\tたべる\t動詞\t朝はパンを食べた。"));
+        // どの語にも、その語が出てくる文が例文として付く
+        assert!(rows.iter().all(|row| {
+            let fields: Vec<&str> = row.split('\t').collect();
+            !fields[3].is_empty()
+        }));
+        assert!(!tsv.contains("\n\n"));
+        assert_eq!(tsv_field("本を\t読む\n"), "本を 読む");
+        Ok(())
+    }
+
+    #[test]
+    fn test_word_base_form() -> Result<(), Box<dyn std::error::Error>> {
+        let base_forms = |text: &str| -> Result<Vec<(String, Option<String>)>, BunsetsuError> {
+            Ok(analyze_text(text.to_string(), None)?
+                .into_iter()
+                .map(|word| (word.text, word.base_form))
+                .collect())
+        };
+        let words = base_forms("走った")?;
+        assert_eq!(words[0], ("走っ".to_string(), Some("走る".to_string())));
+
+        let words = base_forms("美しく咲く")?;
+        assert_eq!(words[0], ("美しく".to_string(), Some("美しい".to_string())));
+
+        // 活用しない語は表記と同じ、記号は None
+        let words = base_forms("本を読む。")?;
+        assert_eq!(words[0], ("本".to_string(), Some("本".to_string())));
+        assert_eq!(words[1], ("を".to_string(), Some("を".to_string())));
+        assert_eq!(words.last().and_then(|(_, base)| base.clone()), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_katakana_loanword() -> Result<(), Box<dyn std::error::Error>> {
+        let category = |text: &str| -> Result<Option<String>, BunsetsuError> {
+            Ok(analyze_text(text.to_string(), None)?
+                .into_iter()
+                .next()
+                .and_then(|word| word.category))
+        };
+        assert_eq!(category("コンピュータ")?.as_deref(), Some("loanword"));
+        assert_eq!(category("ガタガタ")?, None);
+        assert_eq!(category("東京")?, None);
+
+        // 一語になった混ざった表記はカタカナの部分で判定する
+        let info = |text: &str, features: &[&str]| TokenInfo {
+            text: text.to_string(),
+            features: features.iter().map(|s| s.to_string()).collect(),
+            layout: FeatureLayout::for_kind(&DictionaryKind::IPADIC),
+            byte_start: 0,
+        };
+        assert!(is_katakana_loanword(&info(
+            "コーヒー牛乳",
+            &["名詞", "一般"]
+        )));
+        assert!(!is_katakana_loanword(&info("和風パン", &["名詞", "一般"])));
+        assert!(!is_katakana_loanword(&info("キラキラ", &["名詞", "一般"])));
+        assert!(!is_katakana_loanword(&info(
+            "トヨタ",
+            &["名詞", "固有名詞"]
+        )));
+        Ok(())
+    }
+
+    #[test]
+    fn test_token_cache_hit() -> Result<(), Box<dyn std::error::Error>> {
+        // 辞書の入れ替え・破棄でキャッシュが空にならないようにする
+        let _lifecycle = lock_tokenizer_lifecycle();
+        // 他のテストと重ならないテキストを使う
+        let text = "キャッシュの確認用に同じ段落を二度解析する";
+        let first = split_text_into_bunsetsu(text.to_string())?;
+        let hits = cache_hits();
+        let second = split_text_into_bunsetsu(text.to_string())?;
+        assert!(cache_hits() > hits);
+        assert_eq!(first, second);
+        // キャッシュから返すトークン列は複製せず共有する
+        assert!(Arc::ptr_eq(
+            &tokenize_to_infos(text)?,
+            &tokenize_to_infos(text)?
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_token_lru_evicts_least_recently_used() {
+        let mut lru = TokenLru {
+            capacity: 2,
+            entries: VecDeque::new(),
+            hits: 0,
+        };
+        lru.insert("a", Arc::new(Vec::new()));
+        lru.insert("b", Arc::new(Vec::new()));
+        // a を使うと、次に捨てられるのは b になる
+        assert!(lru.get("a").is_some());
+        lru.insert("c", Arc::new(Vec::new()));
+        assert!(lru.get("b").is_none());
+        assert!(lru.get("a").is_some());
+        assert!(lru.get("c").is_some());
+        assert_eq!(lru.hits, 3);
+
+        lru.capacity = 0;
+        lru.entries.clear();
+        lru.insert("a", Arc::new(Vec::new()));
+        assert!(lru.get("a").is_none());
+    }
+
+    #[test]
+    fn test_batch_keeps_order_and_errors() -> Result<(), Box<dyn std::error::Error>> {
+        let texts = vec![
+            "本を読む".to_string(),
+            "".to_string(),
+            "明日は晴れる".to_string(),
+        ];
+
+        let results = split_text_into_bunsetsu_batch(texts.clone());
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0], split_text_into_bunsetsu("本を読む".to_string()));
+        assert_eq!(results[1], Err(BunsetsuError::EmptyInput));
+        assert_eq!(
+            results[2],
+            split_text_into_bunsetsu("明日は晴れる".to_string())
+        );
+
+        let results = analyze_text_batch(texts, None);
+        assert_eq!(results.len(), 3);
+        let surfaces = |words: &[WordInfo]| -> String {
+            words.iter().map(|word| word.text.as_str()).collect()
+        };
+        assert_eq!(
+            results[0].as_deref().map(surfaces),
+            Ok("本を読む".to_string())
+        );
+        assert!(matches!(results[1], Err(BunsetsuError::EmptyInput)));
+        assert_eq!(
+            results[2].as_deref().map(surfaces),
+            Ok("明日は晴れる".to_string())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_quote_mode() -> Result<(), Box<dyn std::error::Error>> {
+        let text = "彼は「『本当に』と言った」と話した";
+        let bunsetsu = split_text_into_bunsetsu(text.to_string())?;
+        assert_eq!(bunsetsu.concat(), text);
+
+        let options = BoundaryOptions {
+            quote_mode: QuoteMode::KeepShort,
+            ..Default::default()
+        };
+        let bunsetsu = split_text_into_bunsetsu_with_options(text.to_string(), &options)?;
+        assert_eq!(bunsetsu.concat(), text);
+        assert!(bunsetsu.contains(&"「『本当に』と言った」".to_string()));
+
+        // 長い引用は通常どおり分ける
+        let text = "「明日は朝から図書館で静かに勉強します」と言った";
+        let bunsetsu = split_text_into_bunsetsu_with_options(text.to_string(), &options)?;
+        assert_eq!(bunsetsu.concat(), text);
+        assert!(bunsetsu.len() > 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unterminated_quote() -> Result<(), Box<dyn std::error::Error>> {
+        for text in ["彼は「", "本を読んだ『", "「"] {
+            let bunsetsu = split_text_into_bunsetsu(text.to_string())?;
+            assert_eq!(bunsetsu.concat(), text);
+            assert!(bunsetsu.len() == 1 || !bunsetsu.contains(&"「".to_string()));
+        }
+
+        // 閉じられない括弧があっても、その前の引用は対応を取る
+        let options = BoundaryOptions {
+            quote_mode: QuoteMode::KeepShort,
+            ..Default::default()
+        };
+        let text = "「はい」と答えた「";
+        let bunsetsu = split_text_into_bunsetsu_with_options(text.to_string(), &options)?;
+        assert_eq!(bunsetsu.concat(), text);
+        assert_eq!(bunsetsu.first().map(String::as_str), Some("「はい」"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_chunk_for_rsvp() -> Result<(), Box<dyn std::error::Error>> {
+        let texts = |chunks: Vec<RsvpChunk>| -> Vec<String> {
+            chunks.into_iter().map(|chunk| chunk.text).collect()
+        };
+
+        // 閉じ括弧の後ろで区切られた「を」を前の名詞のまとまりに戻す
+        let separate = RsvpOptions {
+            merge_short_particles: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            texts(chunk_text_for_rsvp("「本」を読む".to_string(), &separate)?),
+            vec!["「本」", "を", "読む"]
+        );
+        assert_eq!(
+            texts(chunk_text_for_rsvp(
+                "「本」を読む".to_string(),
+                &RsvpOptions::default()
+            )?),
+            vec!["「本」を", "読む"]
+        );
+
+        // 分けられない長いトークンは文字数以上に長く表示する
+        let options = RsvpOptions {
+            max_chars: 8,
+            ..Default::default()
+        };
+        let word = "supercalifragilistic";
+        let chunks = chunk_text_for_rsvp(word.to_string(), &options)?;
+        assert_eq!(chunks.len(), 1);
+        assert!(chunks[0].display_ms > RSVP_BASE_MS + word.len() as u64 * RSVP_MS_PER_CHAR);
+        Ok(())
+    }
+
+    #[test]
+    fn test_estimate_text_duration() -> Result<(), Box<dyn std::error::Error>> {
+        let options = SpeechDurationOptions::default();
+        let short = estimate_text_duration("本を読む。".to_string(), &options)?;
+        let long =
+            estimate_text_duration("本を読む。新聞も毎朝ゆっくり読む。".to_string(), &options)?;
+        assert!(short > 0.0);
+        assert!(long > short);
+        Ok(())
+    }
+
+    #[test]
+    fn test_adverb_with_to() -> Result<(), Box<dyn std::error::Error>> {
+        let bunsetsu = split_text_into_bunsetsu("ゆっくりと歩く".to_string())?;
+        assert_eq!(bunsetsu, vec!["ゆっくりと", "歩く"]);
+
+        // 引用の「と」と取り違えない
+        let bunsetsu = split_text_into_bunsetsu("ゆっくりと考える".to_string())?;
+        assert_eq!(bunsetsu, vec!["ゆっくりと", "考える"]);
+
+        let bunsetsu = split_text_into_bunsetsu("突然と".to_string())?;
+        assert_eq!(bunsetsu, vec!["突然と"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_group_words_by_pos() -> Result<(), Box<dyn std::error::Error>> {
+        let groups = group_words_by_pos("本を食べた。パンも食べる。".to_string())?;
+        assert_eq!(groups["verbs"], vec!["食べる"]);
+        assert_eq!(groups["nouns"], vec!["本", "パン"]);
+        assert!(groups
+            .iter()
+            .filter(|(category, _)| category.as_str() != "verbs")
+            .all(|(_, words)| !words.iter().any(|word| word == "食べる")));
+        assert!(!groups.contains_key("symbols"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_adverbial_noun_suffix() -> Result<(), Box<dyn std::error::Error>> {
+        let bunsetsu = split_text_into_bunsetsu("会議中に電話が来た".to_string())?;
+        assert_eq!(bunsetsu, vec!["会議中に", "電話が", "来た"]);
+
+        let bunsetsu = split_text_into_bunsetsu("食事後の".to_string())?;
+        assert_eq!(bunsetsu, vec!["食事後の"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_difficulty_label_for_score() {
+        assert_eq!(difficulty_label_for_score(0.1), "初級");
+        assert_eq!(difficulty_label_for_score(0.25), "中級");
+        assert_eq!(difficulty_label_for_score(0.3), "中級");
+        assert_eq!(difficulty_label_for_score(0.45), "上級");
+        assert_eq!(difficulty_label_for_score(0.8), "上級");
+    }
+
+    #[test]
+    fn test_difficulty_label_of_text() -> Result<(), Box<dyn std::error::Error>> {
+        assert_eq!(
+            difficulty_label_of_text("ねこがいる。".to_string())?,
+            "初級"
+        );
+        assert_eq!(
+            difficulty_label_of_text(
+                "国際経済情勢の急激な変化に伴い、政府は金融緩和政策の見直しを検討している。"
+                    .to_string()
+            )?,
+            "上級"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_de_senses() -> Result<(), Box<dyn std::error::Error>> {
+        // 格助詞（場所）
+        let bunsetsu = split_text_into_bunsetsu("東京で働く".to_string())?;
+        assert_eq!(bunsetsu, vec!["東京で", "働く"]);
+
+        // 断定の助動詞「だ」の連用形
+        let bunsetsu = split_text_into_bunsetsu("静かで綺麗だ".to_string())?;
+        assert_eq!(bunsetsu, vec!["静かで", "綺麗だ"]);
+
+        // 接続助詞
+        let bunsetsu = split_text_into_bunsetsu("読んで帰る".to_string())?;
+        assert_eq!(bunsetsu, vec!["読んで", "帰る"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_with_offsets() -> Result<(), Box<dyn std::error::Error>> {
+        let text = "雨の降る日、家にいる";
+        let phrases = split_text_with_offsets(text.to_string())?;
+        let chars: Vec<char> = text.chars().collect();
+
+        let mut expected_start = 0;
+        for (phrase, start, end) in &phrases {
+            assert_eq!(*start, expected_start);
+            assert_eq!(chars[*start..*end].iter().collect::<String>(), *phrase);
+            expected_start = *end;
+        }
+        assert_eq!(expected_start, chars.len());
+
+        // 縮約形の展開・文字の正規化を有効にしても、位置は元のテキストを覆う
+        let text = "ｶﾞｰﾃﾞﾝで本を読んでる";
+        let rules = BoundaryOptions {
+            expand_contractions: true,
+            normalize: Some(NormalizeOptions::default()),
+            ..Default::default()
+        };
+        let phrases = offset_phrases(text.to_string(), &rules)?;
+        let chars: Vec<char> = text.chars().collect();
+        let mut expected_start = 0;
+        for (phrase, start, end) in &phrases {
+            assert_eq!(*start, expected_start);
+            assert_eq!(chars[*start..*end].iter().collect::<String>(), *phrase);
+            expected_start = *end;
+        }
+        assert_eq!(expected_start, chars.len());
+
+        // 文ごとに分ける場合も、空行を含めてテキスト全体を覆う
+        let text = "本を読む。\n\n寝る\n";
+        let rules = BoundaryOptions {
+            split_by_sentence: true,
+            ..Default::default()
+        };
+        let phrases = offset_phrases(text.to_string(), &rules)?;
+        let chars: Vec<char> = text.chars().collect();
+        let mut expected_start = 0;
+        for (phrase, start, end) in &phrases {
+            assert_eq!(*start, expected_start);
+            assert_eq!(chars[*start..*end].iter().collect::<String>(), *phrase);
+            expected_start = *end;
+        }
+        assert_eq!(expected_start, chars.len());
+        assert!(phrases
+            .last()
+            .is_some_and(|(phrase, _, _)| phrase.starts_with("寝る")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_emphatic_no_repetition() -> Result<(), Box<dyn std::error::Error>> {
+        let text = "やるのやらないの";
+        let bunsetsu = split_text_into_bunsetsu(text.to_string())?;
+        assert!(bunsetsu.iter().all(|phrase| !phrase.is_empty()));
+        assert_eq!(bunsetsu.concat(), text);
+        assert_eq!(bunsetsu[0], "やるの");
+        Ok(())
+    }
+
+    // デバッグ出力の切り替えはプロセス全体に効くので、切り替えるテストはこのロックを取ってから動かす
+    static DEBUG_LOGGING_LOCK: Mutex<()> = Mutex::new(());
+
+    // デバッグ出力の設定を切り替える（戻り値を破棄すると元の設定に戻し、ロックを放す）
+    struct DebugLoggingSetting {
+        previous: bool,
+        _lock: MutexGuard<'static, ()>,
+    }
+
+    impl Drop for DebugLoggingSetting {
+        fn drop(&mut self) {
+            set_debug_logging(self.previous);
+        }
+    }
+
+    fn override_debug_logging(enabled: bool) -> DebugLoggingSetting {
+        let lock = DEBUG_LOGGING_LOCK
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let previous = debug_logging_enabled();
+        set_debug_logging(enabled);
+        DebugLoggingSetting {
+            previous,
+            _lock: lock,
+        }
+    }
+
+    #[test]
+    fn test_debug_logging_is_opt_in() -> Result<(), Box<dyn std::error::Error>> {
+        let take_output = || DEBUG_OUTPUT.with(|output| std::mem::take(&mut *output.borrow_mut()));
+        take_output();
+
+        {
+            let _logging = override_debug_logging(false);
+            split_text_into_bunsetsu("本を読む".to_string())?;
+        }
+        assert!(take_output().is_empty());
+
+        {
+            let _logging = override_debug_logging(true);
+            split_text_into_bunsetsu("本を読む".to_string())?;
+        }
+        let output = take_output();
+        assert!(output.iter().any(|line| line.starts_with("境界判定")));
+        assert!(output.iter().any(|line| line.starts_with("文節確定")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream_phrases_matches_batch() -> Result<(), Box<dyn std::error::Error>> {
+        let text = "今日は晴れ。明日は雨が降るでしょう。";
+        let mut events = Vec::new();
+        stream_text_phrases(text.to_string(), |event| {
+            events.push(event);
+            Ok(())
+        })?;
+
+        let options = BoundaryOptions {
+            split_by_sentence: true,
+            ..active_boundary_rules()
+        };
+        let batch = split_text_into_bunsetsu_with_options(text.to_string(), &options)?;
+        let streamed: Vec<String> = events
+            .iter()
+            .filter_map(|event| match event {
+                PhraseEvent::Phrase { text, .. } => Some(text.clone()),
+                PhraseEvent::Done { .. } => None,
+            })
+            .collect();
+        assert_eq!(streamed, batch);
+        assert_eq!(
+            events.last(),
+            Some(&PhraseEvent::Done { count: batch.len() })
+        );
+
+        // 文字の正規化を有効にしても同じ文節になる
+        let text = "ｶﾞｰﾃﾞﾝで１２３円払った。ＡＢＣを読んでる。";
+        let options = BoundaryOptions {
+            split_by_sentence: true,
+            normalize: Some(NormalizeOptions::default()),
+            expand_contractions: true,
+            ..Default::default()
+        };
+        let mut streamed = Vec::new();
+        stream_phrases_with(text.to_string(), &options, |event| {
+            if let PhraseEvent::Phrase { text, .. } = event {
+                streamed.push(text);
+            }
+            Ok(())
+        })?;
+        let batch = split_text_into_bunsetsu_with_options(text.to_string(), &options)?;
+        assert_eq!(streamed, batch);
+        assert!(streamed.concat().contains("ガーデン"));
+
+        // 空行は直前の文節に含める（一括で分けた場合と同じ）
+        let text = "\n本を読む。\n\n寝る";
+        let mut streamed = Vec::new();
+        stream_phrases_with(text.to_string(), &options, |event| {
+            if let PhraseEvent::Phrase { text, .. } = event {
+                streamed.push(text);
+            }
+            Ok(())
+        })?;
+        let batch = split_text_into_bunsetsu_with_options(text.to_string(), &options)?;
+        assert_eq!(streamed, batch);
+        assert_eq!(streamed.concat(), text);
+        Ok(())
+    }
+
+    // このスレッドの境界判定の規則を上書きする（戻り値を破棄すると元に戻る）
+    struct ThreadBoundaryRules;
+
+    impl Drop for ThreadBoundaryRules {
+        fn drop(&mut self) {
+            TEST_BOUNDARY_RULES.with(|rules| rules.borrow_mut().take());
+        }
+    }
+
+    fn override_boundary_rules(rules: BoundaryOptions) -> ThreadBoundaryRules {
+        TEST_BOUNDARY_RULES.with(|current| *current.borrow_mut() = Some(rules));
+        ThreadBoundaryRules
+    }
+
+    #[test]
+    fn test_commands_follow_active_rules() -> Result<(), Box<dyn std::error::Error>> {
+        let text = "学校では本を読む。\n図書館には行かない";
+        let default_count = split_text_into_bunsetsu(text.to_string())?.len();
+
+        let _rules = override_boundary_rules(BoundaryOptions {
+            split_compound_particles: true,
+            split_by_sentence: true,
+            ..BoundaryOptions::default()
+        });
+        let phrases = split_text_into_bunsetsu(text.to_string())?;
+        assert!(phrases.len() > default_count, "{:?}", phrases);
+
+        // 文節を返すコマンドはどれも同じ規則で区切る
+        let offsets: Vec<String> = split_text_with_offsets(text.to_string())?
+            .into_iter()
+            .map(|(phrase, _, _)| phrase)
+            .collect();
+        assert_eq!(offsets, phrases);
+        assert_eq!(analyze_and_split_text(text.to_string())?.phrases, phrases);
+        let detailed: Vec<String> = split_text_into_detailed(text.to_string())?
+            .into_iter()
+            .map(|bunsetsu| bunsetsu.surface)
+            .collect();
+        assert_eq!(detailed, phrases);
+        let begins = iob_tags_in_text(text.to_string())?
+            .iter()
+            .filter(|token| token.tag == "B")
+            .count();
+        assert_eq!(begins, phrases.len());
+        Ok(())
+    }
+
+    // set_boundary_rules を呼ぶテストはこのロックを取り、終わったら元の規則に戻す
+    static BOUNDARY_RULES_LOCK: Mutex<()> = Mutex::new(());
+
+    struct SavedBoundaryRules {
+        previous: Option<BoundaryOptions>,
+        _lock: MutexGuard<'static, ()>,
+    }
+
+    impl Drop for SavedBoundaryRules {
+        fn drop(&mut self) {
+            let mut rules = BOUNDARY_RULES
+                .write()
+                .unwrap_or_else(PoisonError::into_inner);
+            *rules = self.previous.take();
+        }
+    }
+
+    fn save_boundary_rules() -> SavedBoundaryRules {
+        let lock = BOUNDARY_RULES_LOCK
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let previous = BOUNDARY_RULES
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        SavedBoundaryRules {
+            previous,
+            _lock: lock,
+        }
+    }
+
+    #[test]
+    fn test_boundary_rules_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+        let _saved = save_boundary_rules();
+        let rules = get_boundary_rules();
+        assert_eq!(rules["split_compound_particles"], false);
+
+        set_boundary_rules(rules.clone())?;
+        assert_eq!(get_boundary_rules(), rules);
+
+        // 解釈できない値は受け付けず、現在の設定も変えない
+        let invalid = serde_json::json!({ "split_compound_particles": "yes" });
+        assert!(matches!(
+            set_boundary_rules(invalid),
+            Err(BunsetsuError::InvalidArgument(_))
+        ));
+        assert_eq!(get_boundary_rules(), rules);
+        Ok(())
+    }
+
+    #[test]
+    fn test_clause_split_by_kara_sense() -> Result<(), Box<dyn std::error::Error>> {
+        // 理由の「から」（接続助詞）は節を区切る
+        let clauses = split_text_into_clauses("疲れたから休む".to_string())?;
+        assert_eq!(clauses, vec!["疲れたから", "休む"]);
+
+        // 起点の「から」（格助詞）は文節を区切るが節は区切らない
+        assert_eq!(
+            split_text_into_bunsetsu("東京から来た".to_string())?,
+            vec!["東京から", "来た"]
+        );
+        let clauses = split_text_into_clauses("東京から来た".to_string())?;
+        assert_eq!(clauses, vec!["東京から来た"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream_hash_concatenates() {
+        let token = |text: &str, pos: &str| TokenInfo {
+            text: text.to_string(),
+            features: vec![pos.to_string()],
+            layout: FeatureLayout::for_kind(&DictionaryKind::IPADIC),
+            byte_start: 0,
+        };
+        let tokens = [
+            token("本", "名詞"),
+            token("を", "助詞"),
+            token("読む", "動詞"),
+        ];
+        let whole = StreamHash::of_tokens(&tokens);
+        for split in 0..=tokens.len() {
+            let (head, tail) = tokens.split_at(split);
+            assert_eq!(
+                StreamHash::of_tokens(head).then(StreamHash::of_tokens(tail)),
+                whole
+            );
+        }
+        assert_eq!(StreamHash::decode(&whole.encode()), Some(whole));
+        assert_ne!(whole, StreamHash::of_tokens(&tokens[..2]));
+    }
+
+    #[test]
+    fn test_split_result_token_hash() -> Result<(), Box<dyn std::error::Error>> {
+        let a = split_text_into_result("本を読む".to_string())?;
+        let b = split_text_into_result("本を読む".to_string())?;
+        assert_eq!(a.token_hash, b.token_hash);
+        assert_eq!(a.token_hash.len(), 16);
+
+        let c = split_text_into_result("本を読め".to_string())?;
+        assert_ne!(a.token_hash, c.token_hash);
+
+        assert_eq!(a.phrase_hashes.len(), a.phrases.len());
+
+        // 結合では継ぎ目の文節だけを解析し直す。ほかの文節のハッシュはそのまま引き継ぐ
+        let prev_hashes = a.phrase_hashes.clone();
+        let seam_text = format!("{}。", a.phrases.last().map(String::as_str).unwrap_or(""));
+        let merged = merge_results(a, split_text_into_result("。".to_string())?)?;
+        let seam = split_text_into_result(seam_text)?;
+        let mut expected = prev_hashes[..prev_hashes.len() - 1].to_vec();
+        expected.extend(seam.phrase_hashes);
+        assert_eq!(merged.phrase_hashes, expected);
+
+        // 結合した結果のハッシュは、つなげたテキストをまとめて分けた結果のハッシュと同じ
+        for (first, second) in [("本を", "読む"), ("本を読む。", "水を飲む。")] {
+            let merged = merge_results(
+                split_text_into_result(first.to_string())?,
+                split_text_into_result(second.to_string())?,
+            )?;
+            let whole = split_text_into_result(format!("{}{}", first, second))?;
+            assert_eq!(merged.token_hash, whole.token_hash);
+            assert_eq!(merged.phrases, whole.phrases);
+        }
+
+        // 文節ごとのハッシュを持たない以前の形式の結果も、テキスト全体を解析したハッシュになる
+        let legacy: SplitResult = serde_json::from_value(serde_json::json!({
+            "text": "本を読む",
+            "phrases": ["本を", "読む"],
+        }))?;
+        let merged = merge_results(legacy, split_text_into_result("。".to_string())?)?;
+        assert_eq!(merged.phrase_hashes.len(), merged.phrases.len());
+        assert_eq!(
+            merged.token_hash,
+            split_text_into_result("本を読む。".to_string())?.token_hash
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_sou_da_attaches_to_predicate() -> Result<(), Box<dyn std::error::Error>> {
+        // 伝聞（終止形＋そうだ）
+        let bunsetsu = split_text_into_bunsetsu("美味しいそうだ".to_string())?;
+        assert_eq!(bunsetsu, vec!["美味しいそうだ"]);
+
+        // 様態（連用形＋そうだ）。句点の前でだけ区切る
+        let bunsetsu = split_text_into_bunsetsu("雨が降りそうだ。".to_string())?;
+        assert_eq!(bunsetsu, vec!["雨が", "降りそうだ", "。"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_negative_volitional_mai() -> Result<(), Box<dyn std::error::Error>> {
+        let bunsetsu = split_text_into_bunsetsu("行くまい".to_string())?;
+        assert_eq!(bunsetsu, vec!["行くまい"]);
+
+        let bunsetsu = split_text_into_bunsetsu("二度と行くまいと思った".to_string())?;
+        assert!(bunsetsu.iter().any(|phrase| phrase.starts_with("行くまい")));
+        assert!(!bunsetsu.contains(&"まい".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_token_char_counts() -> Result<(), Box<dyn std::error::Error>> {
+        let counts = token_char_counts_in_text("Tシャツ3枚".to_string())?;
+        assert_eq!(
+            counts.iter().map(|c| c.text.as_str()).collect::<String>(),
+            "Tシャツ3枚"
+        );
+
+        let total = |f: fn(&TokenCharCounts) -> usize| counts.iter().map(f).sum::<usize>();
+        assert_eq!(total(|c| c.latin), 1);
+        assert_eq!(total(|c| c.katakana), 3);
+        assert_eq!(total(|c| c.digit), 1);
+        assert_eq!(total(|c| c.kanji), 1);
+        assert_eq!(total(|c| c.hiragana), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_empty_phrases() -> Result<(), Box<dyn std::error::Error>> {
+        for text in ["、本を読む", "。 本を読む", " 本を読む。 ", "「」"] {
+            let bunsetsu = split_text_into_bunsetsu(text.to_string())?;
+            assert!(
+                bunsetsu.iter().all(|phrase| !phrase.trim().is_empty()),
+                "{:?}",
+                bunsetsu
+            );
+            assert_eq!(bunsetsu.concat(), text);
+        }
+
+        assert!(split_text_into_bunsetsu("   ".to_string())?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_reading_coverage() -> Result<(), Box<dyn std::error::Error>> {
+        // 「龘」は辞書にない
+        let coverage = reading_coverage_of_text("漢字と龘".to_string())?;
+        assert!((coverage - 2.0 / 3.0).abs() < 1e-9);
+
+        assert_eq!(reading_coverage_of_text("漢字".to_string())?, 1.0);
+        assert_eq!(reading_coverage_of_text("ひらがな".to_string())?, 1.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_token_ambiguities() -> Result<(), Box<dyn std::error::Error>> {
+        // 複合語は分解モードのほうが細かく分かれる。区切り方そのものは辞書によるので確かめない
+        let text = "関西国際空港に行く";
+        let ambiguities = token_ambiguities_in_text(text.to_string())?;
+        assert_eq!(
+            ambiguities
+                .iter()
+                .map(|token| token.text.as_str())
+                .collect::<String>(),
+            text
+        );
+        let airport = ambiguities
+            .iter()
+            .find(|token| token.text.contains("空港"))
+            .ok_or("空港を含むトークンがない")?;
+        assert!(airport.ambiguous, "{:?}", ambiguities);
+        assert!(airport.alternatives.len() > 1);
+        assert_eq!(airport.alternatives.concat(), airport.text);
+        // 分解モードのほうがトークンが多い
+        let decomposed: usize = ambiguities
+            .iter()
+            .map(|token| token.alternatives.len())
+            .sum();
+        assert!(decomposed > ambiguities.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_decompose_tokenizer_is_cached() -> Result<(), Box<dyn std::error::Error>> {
+        let _lifecycle = lock_tokenizer_lifecycle();
+        let loaded = get_tokenizer()?;
+        let first = get_decompose_tokenizer(&loaded)?;
+        let second = get_decompose_tokenizer(&loaded)?;
+        assert!(Arc::ptr_eq(&first, &second));
+
+        // トークナイザを破棄すると分解モードのトークナイザも手放す
+        unload_tokenizer()?;
+        assert!(DECOMPOSE_TOKENIZER
+            .read()
+            .map_err(|e| e.to_string())?
+            .is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_readings() -> Result<(), Box<dyn std::error::Error>> {
+        // 「龘」は辞書にないので、それを含む語だけが一覧に載る
+        let missing = missing_readings_in_text("龘龘の話と龘龘".to_string())?;
+        assert!(!missing.is_empty());
+        assert!(missing
+            .iter()
+            .all(|entry| entry.text.chars().all(|c| c == '龘')));
+
+        let mut covered: Vec<usize> = missing
+            .iter()
+            .flat_map(|entry| entry.positions.iter().flat_map(|&(start, end)| start..end))
+            .collect();
+        covered.sort_unstable();
+        assert_eq!(covered, vec![0, 1, 5, 6]);
+
+        assert!(missing_readings_in_text("漢字の話".to_string())?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_attributive_na_before_node_noni() -> Result<(), Box<dyn std::error::Error>> {
+        let bunsetsu = split_text_into_bunsetsu("静かなので".to_string())?;
+        assert_eq!(bunsetsu, vec!["静かなので"]);
+
+        let bunsetsu = split_text_into_bunsetsu("好きなのに".to_string())?;
+        assert_eq!(bunsetsu, vec!["好きなのに"]);
+
+        let bunsetsu = split_text_into_bunsetsu("静かなので眠れる".to_string())?;
+        assert_eq!(bunsetsu, vec!["静かなので", "眠れる"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_prepared_document_window() -> Result<(), Box<dyn std::error::Error>> {
+        // 雨の/降る/日、/家に/いる
+        prepare_document("doc-test".to_string(), "雨の降る日、家にいる".to_string())?;
+
+        let window = document_phrases_in_range("doc-test", 4, 7)?;
+        let texts: Vec<&str> = window.iter().map(|(text, _, _)| text.as_str()).collect();
+        assert_eq!(texts, vec!["日、", "家に"]);
+        assert_eq!((window[0].1, window[0].2), (4, 6));
+
+        release_document("doc-test")?;
+        assert_eq!(
+            document_phrases_in_range("doc-test", 0, 10),
+            Err(BunsetsuError::UnknownDocument("doc-test".to_string()))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_suffix_like_nouns() -> Result<(), Box<dyn std::error::Error>> {
+        let bunsetsu = split_text_into_bunsetsu("泥だらけ".to_string())?;
+        assert_eq!(bunsetsu, vec!["泥だらけ"]);
+
+        let bunsetsu = split_text_into_bunsetsu("間違いだらけ".to_string())?;
+        assert_eq!(bunsetsu, vec!["間違いだらけ"]);
+
+        let bunsetsu = split_text_into_bunsetsu("ほこりまみれになる".to_string())?;
+        assert_eq!(bunsetsu, vec!["ほこりまみれに", "なる"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_analyze_and_split_matches_separate_calls() -> Result<(), Box<dyn std::error::Error>> {
+        let text = "雨の降る日、家にいる";
+        let combined = analyze_and_split_text(text.to_string())?;
+        assert_eq!(combined.words, analyze_text(text.to_string(), None)?);
+        assert_eq!(
+            combined.phrases,
+            split_text_into_bunsetsu(text.to_string())?
+        );
+
+        for (phrase, range) in combined.phrases.iter().zip(&combined.phrase_token_ranges) {
+            let joined: String = combined.words[range.clone()]
+                .iter()
+                .map(|word| word.text.as_str())
+                .collect();
+            assert_eq!(&joined, phrase);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_explanatory_noda() -> Result<(), Box<dyn std::error::Error>> {
+        let bunsetsu = split_text_into_bunsetsu("行くんだ".to_string())?;
+        assert_eq!(bunsetsu, vec!["行くんだ"]);
+
+        let bunsetsu = split_text_into_bunsetsu("行くのだ。".to_string())?;
+        assert_eq!(bunsetsu, vec!["行くのだ", "。"]);
+
+        let bunsetsu = split_text_into_bunsetsu("大変なんです".to_string())?;
+        assert_eq!(bunsetsu, vec!["大変なんです"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_word_frequencies_fold_latin_case() -> Result<(), Box<dyn std::error::Error>> {
+        let text = "APIとapiを使う".to_string();
+
+        let frequencies = word_frequencies_in_text(text.clone(), &FrequencyOptions::default())?;
+        assert_eq!(frequencies.get("API"), Some(&1));
+        assert_eq!(frequencies.get("api"), Some(&1));
+
+        let options = FrequencyOptions {
+            fold_latin_case: true,
+            ..Default::default()
+        };
+        let frequencies = word_frequencies_in_text(text, &options)?;
+        assert_eq!(frequencies.get("api"), Some(&2));
+        assert_eq!(frequencies.get("API"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_concurrent_get_tokenizer_initializes_once() -> Result<(), Box<dyn std::error::Error>> {
+        let _lifecycle = lock_tokenizer_lifecycle();
+        // 未初期化の状態から 16 スレッドで同時に取得する
+        unload_tokenizer()?;
+
+        let barrier = Arc::new(Barrier::new(16));
+        let handles: Vec<_> = (0..16)
+            .map(|_| {
+                let barrier = Arc::clone(&barrier);
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    get_tokenizer().map_err(|e| e.to_string())
+                })
+            })
+            .collect();
+        let tokenizers = handles
+            .into_iter()
+            .map(|handle| handle.join().expect("tokenizer thread panicked"))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // 全スレッドが同じインスタンスを受け取っていれば、初期化は一度だけ
+        assert!(tokenizers
+            .iter()
+            .all(|tokenizer| Arc::ptr_eq(tokenizer, &tokenizers[0])));
+
+        // 破棄して読み込み直しても、組み込みの辞書は create_tokenizer が返すものと同じ
+        let builtin = create_tokenizer(DictionaryKind::IPADIC)?;
+        assert!(std::ptr::eq(tokenizers[0].tokenizer(), builtin));
+        let builtins = (0..16)
+            .map(|_| {
+                std::thread::spawn(|| {
+                    builtin_tokenizer(&DictionaryKind::IPADIC)
+                        .map(|t| t as *const Tokenizer as usize)
+                        .map_err(|e| e.to_string())
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|handle| handle.join().expect("tokenizer thread panicked"))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert!(builtins
+            .iter()
+            .all(|&address| address == builtin as *const Tokenizer as usize));
+        Ok(())
+    }
+
+    // 辞書ごとに独立したトークナイザで解析し、各トークンの読みを返す
+    fn readings_with(
+        kind: DictionaryKind,
+        text: &str,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let loaded = build_tokenizer(kind, None)?;
+        Ok(tokenize_with(&loaded, text)?
+            .iter()
+            .map(|info| info.reading().unwrap_or("").to_string())
+            .collect())
+    }
+
+    #[test]
+    fn test_ipadic_readings() -> Result<(), Box<dyn std::error::Error>> {
+        let readings = readings_with(DictionaryKind::IPADIC, "本を読む")?;
+        assert_eq!(readings, ["ホン", "ヲ", "ヨム"]);
+        Ok(())
+    }
+
+    #[cfg(feature = "unidic")]
+    #[test]
+    fn test_unidic_readings() -> Result<(), Box<dyn std::error::Error>> {
+        let readings = readings_with(DictionaryKind::UniDic, "本を読む")?;
+        assert!(!readings.is_empty());
+        assert!(readings
+            .iter()
+            .all(|reading| !reading.is_empty() && reading != "*"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_user_dictionary_keeps_compound() -> Result<(), Box<dyn std::error::Error>> {
+        let path =
+            std::env::temp_dir().join(format!("easy-reading-userdic-{}.csv", std::process::id()));
+        std::fs::write(&path, "心筋梗塞,カスタム名詞,シンキンコウソク\n")?;
+
+        let result = validate_user_dictionary(&path).and_then(|_| {
+            let loaded = build_tokenizer(DictionaryKind::IPADIC, Some(path.clone()))?;
+            tokenize_to_texts(&loaded, "心筋梗塞の治療")
+        });
+        std::fs::remove_file(&path)?;
+
+        assert_eq!(result?.first().map(String::as_str), Some("心筋梗塞"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_malformed_user_dictionary() -> Result<(), Box<dyn std::error::Error>> {
+        let _lifecycle = lock_tokenizer_lifecycle();
+        let path = std::env::temp_dir().join(format!(
+            "easy-reading-userdic-malformed-{}.csv",
+            std::process::id()
+        ));
+        std::fs::write(
+            &path,
+            "心筋梗塞,カスタム名詞,シンキンコウソク\n心筋炎,名詞\n",
+        )?;
+
+        // 検査で弾かれるので、読み込み済みのトークナイザは置き換わらない
+        let result = create_tokenizer_with_user_dict(&path);
+        std::fs::remove_file(&path)?;
+
+        match result {
+            Err(BunsetsuError::DictionaryLoad(message)) => {
+                assert!(message.contains("2 行目"), "{}", message)
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        Ok(())
+    }
+
+    fn tokenize_to_texts(
+        loaded: &LoadedTokenizer,
+        text: &str,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        Ok(tokenize_with(loaded, text)?
+            .into_iter()
+            .map(|info| info.text)
+            .collect())
     }
 }
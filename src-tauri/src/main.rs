@@ -4,15 +4,487 @@
 )]
 
 mod bunsetsu_handler;
+mod kanji_grade;
+mod romaji;
 
-use bunsetsu_handler::{split_text_into_bunsetsu};
+use bunsetsu_handler::{
+    analyze_and_split_text, analyze_sentence_structure, analyze_sentences_in_text, analyze_text,
+    anki_tsv_of_text, annotate_text_with_lookup_links, available_dictionary_kinds, boundary_vector,
+    chunk_text_for_rsvp, compute_readability, count_text_mora, default_acronym_readings,
+    difficulty_label_of_text, document_phrases_in_range, estimate_text_duration,
+    estimate_text_reading_time, format_text_for_print, furigana_spans_in_range, group_words_by_pos,
+    homophone_groups_in_text, iob_tags_in_text, load_acronym_readings, merge_results,
+    missing_readings_in_text, parse_dependencies_in_text, phrase_heads_in_text,
+    phrase_scripts_in_text, pos_transitions_in_text, ra_nuki_forms_in_text,
+    reading_coverage_of_text, romaji_of_text, ruby_annotations_in_text, ruby_segments_in_text,
+    score_segmentation, select_dictionary, split_text_into_bunsetsu,
+    split_text_into_bunsetsu_batch, split_text_into_bunsetsu_with_options, split_text_into_clauses,
+    split_text_into_detailed, split_text_into_paragraphs, split_text_into_result,
+    split_text_into_sentences, split_text_with_confidence, split_text_with_offsets,
+    split_text_with_trace, stream_text_phrases, text_to_ssml, to_aozora_ruby, to_ruby_html,
+    token_ambiguities_in_text, token_char_counts_in_text, token_difficulties_in_text,
+    truncate_preview_text, vocabulary_in_text, word_frequencies_in_text, AnalyzeAndSplit,
+    BoundaryOptions, Bunsetsu, BunsetsuDep, BunsetsuError, DebugSplit, DictionaryError,
+    FrequencyOptions, FuriganaSpan, MissingReading, PhraseConfidence, PhraseEvent, PhraseHead,
+    PhraseScript, PrintOptions, Readability, ReadingTime, RsvpChunk, RsvpOptions, RubyAnnotation,
+    RubySegment, SegmentationScore, SentenceAnalysis, SentenceStructure, SpeechDurationOptions,
+    SplitResult, SsmlOptions, TaggedToken, TokenAmbiguity, TokenCharCounts, TokenDifficulty,
+    VocabEntry, VocabOptions, WordInfo, DEFAULT_MAX_RUBY_PER_BASE,
+};
+use romaji::RomajiStyle;
+use std::collections::BTreeMap;
 // command属性マクロをインポート
 use tauri::command;
+use tauri::ipc::Channel;
 
 // 文節分割のコマンド
 #[command]
-fn split_bunsetsu(text: String) -> Result<Vec<String>, String> {
-    split_text_into_bunsetsu(text).map_err(|e| e.to_string())
+fn split_bunsetsu(text: String) -> Result<Vec<String>, BunsetsuError> {
+    split_text_into_bunsetsu(text)
+}
+
+// 多数のテキスト（字幕の各行など）をまとめて文節分割するコマンド
+// 結果は入力と同じ順で、テキストごとに成功・失敗を返す
+#[command]
+fn split_bunsetsu_batch(texts: Vec<String>) -> Vec<Result<Vec<String>, BunsetsuError>> {
+    split_text_into_bunsetsu_batch(texts)
+}
+
+// 文節を一つずつチャンネルで送るコマンド（長い文書を少しずつ表示するため）
+// 送るメッセージの形式は PhraseEvent を参照
+#[command]
+async fn stream_bunsetsu(
+    text: String,
+    on_phrase: Channel<PhraseEvent>,
+) -> Result<(), BunsetsuError> {
+    stream_text_phrases(text, |event| {
+        on_phrase
+            .send(event)
+            .map_err(|e| BunsetsuError::Internal(e.to_string()))
+    })
+}
+
+// 文節ごとに構成するトークンと主要語の品詞を返すコマンド
+#[command]
+fn split_bunsetsu_detailed(text: String) -> Result<Vec<Bunsetsu>, BunsetsuError> {
+    split_text_into_detailed(text)
+}
+
+// 文節ごとの係り先（右向きの単純な規則による）を返すコマンド
+#[command]
+fn parse_dependencies(text: String) -> Result<Vec<BunsetsuDep>, BunsetsuError> {
+    parse_dependencies_in_text(text)
+}
+
+// トークンごとの表記と品詞を返すコマンド
+// pos_filter を指定すると、その品詞（名詞 など）のトークンだけを返す
+#[command]
+fn analyze_text_command(
+    text: String,
+    pos_filter: Option<Vec<String>>,
+) -> Result<Vec<WordInfo>, BunsetsuError> {
+    analyze_text(text, pos_filter.as_deref())
+}
+
+// 多数のテキストをまとめて形態素解析するコマンド
+#[command]
+fn analyze_text_batch(
+    texts: Vec<String>,
+    pos_filter: Option<Vec<String>>,
+) -> Vec<Result<Vec<WordInfo>, BunsetsuError>> {
+    bunsetsu_handler::analyze_text_batch(texts, pos_filter.as_deref())
+}
+
+// 形態素解析と文節分割の結果を一度の解析でまとめて返すコマンド
+#[command]
+fn analyze_and_split(text: String) -> Result<AnalyzeAndSplit, BunsetsuError> {
+    analyze_and_split_text(text)
+}
+
+// 文節分割に加えて、各境界の確からしさを返すコマンド
+#[command]
+fn split_bunsetsu_with_confidence(text: String) -> Result<Vec<PhraseConfidence>, BunsetsuError> {
+    split_text_with_confidence(text)
+}
+
+// 文節ごとに元テキストでの位置（文字単位の開始・終了）を付けて返すコマンド
+#[command]
+fn split_bunsetsu_with_offsets(text: String) -> Result<Vec<(String, usize, usize)>, BunsetsuError> {
+    split_text_with_offsets(text)
+}
+
+// テキストを節（接続助詞・文末で区切ったまとまり）に分けるコマンド
+#[command]
+fn split_clauses(text: String) -> Result<Vec<String>, BunsetsuError> {
+    split_text_into_clauses(text)
+}
+
+// 文節分割の結果を、元テキストと合わせて返すコマンド
+#[command]
+fn split_bunsetsu_result(text: String) -> Result<SplitResult, BunsetsuError> {
+    split_text_into_result(text)
+}
+
+// 追記部分だけを解析した結果を既存の結果に結合するコマンド
+#[command]
+fn merge_split_results(
+    prev: SplitResult,
+    appended: SplitResult,
+) -> Result<SplitResult, BunsetsuError> {
+    merge_results(prev, appended)
+}
+
+// テキストのモーラ数を返すコマンド（俳句・川柳の 5・7・5 判定用）
+#[command]
+fn count_mora(text: String) -> Result<usize, BunsetsuError> {
+    count_text_mora(text)
+}
+
+// 内容語を辞書引き用の span で囲んだ HTML を返すコマンド
+#[command]
+fn annotate_lookup_links(text: String) -> Result<String, BunsetsuError> {
+    annotate_text_with_lookup_links(text)
+}
+
+// このビルドで使用できる辞書の一覧を返すコマンド
+#[command]
+fn available_dictionaries() -> Vec<String> {
+    available_dictionary_kinds()
+}
+
+// 使用する辞書を切り替えるコマンド
+#[command]
+fn set_dictionary(kind: String) -> Result<(), DictionaryError> {
+    select_dictionary(&kind)
+}
+
+// 境界判定のオプションを指定して文節分割するコマンド
+#[command]
+fn split_bunsetsu_with_options(
+    text: String,
+    options: BoundaryOptions,
+) -> Result<Vec<String>, BunsetsuError> {
+    split_text_into_bunsetsu_with_options(text, &options)
+}
+
+// 現在の境界判定のオプションを JSON で返すコマンド
+#[command]
+fn get_boundary_rules() -> serde_json::Value {
+    bunsetsu_handler::get_boundary_rules()
+}
+
+// 境界判定のオプションを JSON で上書きするコマンド
+#[command]
+fn set_boundary_rules(rules: serde_json::Value) -> Result<(), BunsetsuError> {
+    bunsetsu_handler::set_boundary_rules(rules)
+}
+
+// 境界判定などのデバッグ出力を標準エラーに書くかどうかを切り替えるコマンド（既定は出力しない）
+#[command]
+fn set_debug_logging(enabled: bool) {
+    bunsetsu_handler::set_debug_logging(enabled);
+}
+
+// 解析結果のキャッシュに保持するテキストの数を変えるコマンド（0 ならキャッシュしない）
+#[command]
+fn set_cache_capacity(n: usize) {
+    bunsetsu_handler::set_cache_capacity(n);
+}
+
+// 解析結果のキャッシュを空にするコマンド
+#[command]
+fn clear_cache() {
+    bunsetsu_handler::clear_cache();
+}
+
+// 境界判定の経過を含めて文節分割するデバッグ用コマンド
+#[command]
+fn debug_split_bunsetsu(text: String, verbose: bool) -> Result<DebugSplit, BunsetsuError> {
+    split_text_with_trace(text, verbose)
+}
+
+// 読み上げ用の SSML を返すコマンド
+#[command]
+fn to_ssml(text: String, options: Option<SsmlOptions>) -> Result<String, BunsetsuError> {
+    text_to_ssml(text, &options.unwrap_or_default())
+}
+
+// トークン間ごとの境界判定結果を返すコマンド
+#[command]
+fn get_boundary_vector(text: String) -> Result<Vec<bool>, BunsetsuError> {
+    boundary_vector(text)
+}
+
+// 同じ読みを持つ異なる表記をまとめて返すコマンド
+#[command]
+fn homophone_groups(text: String) -> Result<BTreeMap<String, Vec<String>>, BunsetsuError> {
+    homophone_groups_in_text(text)
+}
+
+// 青空文庫形式のルビ付きテキストを返すコマンド
+#[command]
+fn aozora_ruby(text: String) -> Result<String, BunsetsuError> {
+    to_aozora_ruby(text)
+}
+
+// min_grade 年生以降に習う漢字にだけ <ruby> を付けた HTML を返すコマンド
+#[command]
+fn ruby_html(text: String, min_grade: u8) -> Result<String, BunsetsuError> {
+    to_ruby_html(text, min_grade)
+}
+
+// テキストをローマ字（既定は伝統的ヘボン式）にするコマンド
+#[command]
+fn to_romaji(text: String, style: Option<RomajiStyle>) -> Result<String, BunsetsuError> {
+    romaji_of_text(text, style.unwrap_or_default())
+}
+
+// 送り仮名を除いた漢字の部分にだけ読みを付けたルビの単位を返すコマンド
+#[command]
+fn generate_furigana(text: String) -> Result<Vec<RubySegment>, BunsetsuError> {
+    ruby_segments_in_text(text)
+}
+
+// ルビの単位ごとに親文字・ルビの文字数と、ルビが長すぎるかどうかを返すコマンド
+// max_ruby_per_base は親文字 1 字あたりのルビの文字数の上限（省略時は 2）
+#[command]
+fn ruby_metrics(
+    text: String,
+    max_ruby_per_base: Option<usize>,
+) -> Result<Vec<RubyAnnotation>, BunsetsuError> {
+    ruby_annotations_in_text(text, max_ruby_per_base.unwrap_or(DEFAULT_MAX_RUBY_PER_BASE))
+}
+
+// 文ごとの構造（述語の有無）を返すコマンド
+#[command]
+fn analyze_structure(text: String) -> Result<Vec<SentenceStructure>, BunsetsuError> {
+    analyze_sentence_structure(text)
+}
+
+// 語ごとの出現回数を返すコマンド
+#[command]
+fn word_frequencies(
+    text: String,
+    options: Option<FrequencyOptions>,
+) -> Result<BTreeMap<String, usize>, BunsetsuError> {
+    word_frequencies_in_text(text, &options.unwrap_or_default())
+}
+
+// 活用した形を辞書形にまとめた語彙リストを返すコマンド
+#[command]
+fn build_vocabulary(
+    text: String,
+    options: Option<VocabOptions>,
+) -> Result<Vec<VocabEntry>, BunsetsuError> {
+    vocabulary_in_text(text, &options.unwrap_or_default())
+}
+
+// 語彙リストを Anki に読み込める TSV（辞書形・読み・品詞・例文）で返すコマンド
+#[command]
+fn export_anki_tsv(text: String) -> Result<String, BunsetsuError> {
+    anki_tsv_of_text(text)
+}
+
+// 指定した文字範囲（表示中の部分）の振り仮名だけを返すコマンド
+#[command]
+fn furigana_spans_range(
+    text: String,
+    start: usize,
+    end: usize,
+) -> Result<Vec<FuriganaSpan>, BunsetsuError> {
+    furigana_spans_in_range(text, start, end)
+}
+
+// 文節ごとの主要語（最初の内容語）を返すコマンド
+#[command]
+fn phrase_heads(text: String) -> Result<Vec<PhraseHead>, BunsetsuError> {
+    phrase_heads_in_text(text)
+}
+
+// トークナイザを破棄して辞書のメモリを解放するコマンド（次の解析時に読み込み直す）
+#[command]
+fn unload_tokenizer() -> Result<(), BunsetsuError> {
+    bunsetsu_handler::unload_tokenizer()
+}
+
+// 正解の文節列と比べた境界の適合率・再現率・F値を返すコマンド
+#[command]
+fn evaluate_segmentation(
+    text: String,
+    gold: Vec<String>,
+) -> Result<SegmentationScore, BunsetsuError> {
+    score_segmentation(text, gold)
+}
+
+// 英字の略語の読みの表を読み込むコマンド（省略時は既定の表）
+#[command]
+fn set_acronym_readings(table: Option<BTreeMap<String, String>>) -> Result<(), BunsetsuError> {
+    load_acronym_readings(table.unwrap_or_else(default_acronym_readings))
+}
+
+// トークンごとに IOB2 形式の文節タグ（B / I）を返すコマンド
+#[command]
+fn tag_iob(text: String) -> Result<Vec<TaggedToken>, BunsetsuError> {
+    iob_tags_in_text(text)
+}
+
+// テキスト全体の読み上げ時間（秒）の見積もりを返すコマンド
+#[command]
+fn estimate_speech_duration(
+    text: String,
+    options: Option<SpeechDurationOptions>,
+) -> Result<f64, BunsetsuError> {
+    estimate_text_duration(text, &options.unwrap_or_default())
+}
+
+// 黙読にかかる時間の見積もりを返すコマンド
+#[command]
+fn estimate_reading_time(
+    text: String,
+    chars_per_minute: f64,
+) -> Result<ReadingTime, BunsetsuError> {
+    estimate_text_reading_time(text, chars_per_minute)
+}
+
+// 品詞の大分類ごとに語の原形をまとめて返すコマンド
+#[command]
+fn words_by_pos(text: String) -> Result<BTreeMap<String, Vec<String>>, BunsetsuError> {
+    group_words_by_pos(text)
+}
+
+// 文章の難易度のラベル（初級・中級・上級）を返すコマンド
+#[command]
+fn difficulty_label(text: String) -> Result<String, BunsetsuError> {
+    difficulty_label_of_text(text)
+}
+
+// 文節をつないだプレビュー（max_chars 文字まで、書記素クラスタ単位）を返すコマンド
+#[command]
+fn truncate_preview(text: String, max_chars: usize) -> Result<String, BunsetsuError> {
+    truncate_preview_text(text, max_chars)
+}
+
+// 文章全体の読みやすさ（文節の平均の長さ・漢字の割合・長い文の割合など）を返すコマンド
+#[command]
+fn readability(text: String) -> Result<Readability, BunsetsuError> {
+    compute_readability(text)
+}
+
+// テキストを文に分けるコマンド（括弧の中の文末記号では区切らない）
+#[command]
+fn split_into_sentences(text: String) -> Vec<String> {
+    split_text_into_sentences(text)
+}
+
+// 段落ごとに文節に分けるコマンド（改行・字下げ・空行は空白だけの要素として残す）
+#[command]
+fn split_bunsetsu_paragraphs(text: String) -> Result<Vec<Vec<String>>, BunsetsuError> {
+    split_text_into_paragraphs(text)
+}
+
+// 文ごとの難易度・黙読時間・文節数をまとめて返すコマンド
+#[command]
+fn analyze_sentences(
+    text: String,
+    chars_per_minute: Option<f64>,
+) -> Result<Vec<SentenceAnalysis>, BunsetsuError> {
+    analyze_sentences_in_text(
+        text,
+        chars_per_minute.unwrap_or(bunsetsu_handler::DEFAULT_CHARS_PER_MINUTE),
+    )
+}
+
+// トークンごとの漢字の難易度（学年・常用・常用外）を返すコマンド
+#[command]
+fn analyze_difficulty(text: String) -> Result<Vec<TokenDifficulty>, BunsetsuError> {
+    token_difficulties_in_text(text)
+}
+
+// 文節ごとの主な文字種（漢字・仮名・英数字・混在）を返すコマンド
+#[command]
+fn phrase_scripts(text: String) -> Result<Vec<PhraseScript>, BunsetsuError> {
+    phrase_scripts_in_text(text)
+}
+
+// トークンごとの文字種（漢字・ひらがな・カタカナ・英字・数字）の文字数を返すコマンド
+#[command]
+fn char_categories(text: String) -> Result<Vec<TokenCharCounts>, BunsetsuError> {
+    token_char_counts_in_text(text)
+}
+
+// 漢字のうち読みの分かるものの割合を返すコマンド
+#[command]
+fn reading_coverage(text: String) -> Result<f64, BunsetsuError> {
+    reading_coverage_of_text(text)
+}
+
+// ら抜き言葉（食べれる など）を返すコマンド
+#[command]
+fn ra_nuki_forms(text: String) -> Result<Vec<String>, BunsetsuError> {
+    ra_nuki_forms_in_text(text)
+}
+
+// ユーザー辞書の CSV を読み込んでトークナイザを初期化し直すコマンド
+#[command]
+fn load_user_dictionary(path: String) -> Result<(), BunsetsuError> {
+    bunsetsu_handler::create_tokenizer_with_user_dict(std::path::Path::new(&path))
+}
+
+// 分かち書き・括弧書きの読み・折り返しをまとめて行った印刷用のテキストを返すコマンド
+#[command]
+fn format_for_print(text: String, opts: Option<PrintOptions>) -> Result<String, BunsetsuError> {
+    format_text_for_print(text, &opts.unwrap_or_default())
+}
+
+// 速読用に一つずつ表示するまとまりと表示時間を返すコマンド
+#[command]
+fn chunk_for_rsvp(
+    text: String,
+    opts: Option<RsvpOptions>,
+) -> Result<Vec<RsvpChunk>, BunsetsuError> {
+    chunk_text_for_rsvp(text, &opts.unwrap_or_default())
+}
+
+// トークンの切り方が曖昧かどうかを返すデバッグ用コマンド（分解モードの解析と比べる）
+#[command]
+fn token_ambiguity(text: String) -> Result<Vec<TokenAmbiguity>, BunsetsuError> {
+    token_ambiguities_in_text(text)
+}
+
+// 読みの分からない漢字を含む語と出現位置を返すコマンド（手で振り仮名を付ける語の一覧）
+#[command]
+fn missing_readings(text: String) -> Result<Vec<MissingReading>, BunsetsuError> {
+    missing_readings_in_text(text)
+}
+
+// 隣り合うトークンの品詞の組ごとの出現回数を返すコマンド（前の品詞 → 後ろの品詞 → 回数）
+#[command]
+fn pos_transition_matrix(
+    text: String,
+) -> Result<BTreeMap<String, BTreeMap<String, usize>>, BunsetsuError> {
+    pos_transitions_in_text(text)
+}
+
+// 文書を解析して文書 ID で保持するコマンド（表示の前にバックグラウンドで実行する）
+#[command]
+async fn prepare_document(doc_id: String, text: String) -> Result<(), BunsetsuError> {
+    bunsetsu_handler::prepare_document(doc_id, text)
+}
+
+// 保持している文書から、文字範囲に掛かる文節を返すコマンド
+#[command]
+fn get_document_phrases(
+    doc_id: String,
+    start: usize,
+    end: usize,
+) -> Result<Vec<(String, usize, usize)>, BunsetsuError> {
+    document_phrases_in_range(&doc_id, start, end)
+}
+
+// 保持している文書の解析結果を破棄するコマンド
+#[command]
+fn release_document(doc_id: String) -> Result<(), BunsetsuError> {
+    bunsetsu_handler::release_document(&doc_id)
 }
 
 fn main() {
@@ -21,7 +493,7 @@ fn main() {
             // 起動時にlinderaトークナイザが初期化できるか確認
             #[cfg(debug_assertions)]
             {
-                match bunsetsu_handler::create_tokenizer() {
+                match bunsetsu_handler::create_tokenizer(bunsetsu_handler::default_dictionary_kind()) {
                     Ok(_) => println!("Lindera tokenizer initialized successfully"),
                     Err(e) => println!("Warning: Lindera initialization error: {}", e),
                 }
@@ -33,8 +505,72 @@ fn main() {
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
         .invoke_handler(tauri::generate_handler![
-            split_bunsetsu
+            split_bunsetsu,
+            split_bunsetsu_batch,
+            split_bunsetsu_detailed,
+            parse_dependencies,
+            stream_bunsetsu,
+            split_bunsetsu_with_confidence,
+            split_bunsetsu_result,
+            merge_split_results,
+            count_mora,
+            annotate_lookup_links,
+            available_dictionaries,
+            set_dictionary,
+            split_bunsetsu_with_options,
+            debug_split_bunsetsu,
+            to_ssml,
+            get_boundary_vector,
+            homophone_groups,
+            aozora_ruby,
+            ruby_html,
+            generate_furigana,
+            ruby_metrics,
+            to_romaji,
+            analyze_structure,
+            word_frequencies,
+            build_vocabulary,
+            export_anki_tsv,
+            furigana_spans_range,
+            phrase_heads,
+            unload_tokenizer,
+            evaluate_segmentation,
+            set_acronym_readings,
+            tag_iob,
+            estimate_speech_duration,
+            estimate_reading_time,
+            words_by_pos,
+            difficulty_label,
+            analyze_difficulty,
+            readability,
+            analyze_sentences,
+            split_into_sentences,
+            split_bunsetsu_paragraphs,
+            phrase_scripts,
+            truncate_preview,
+            split_bunsetsu_with_offsets,
+            get_boundary_rules,
+            set_boundary_rules,
+            set_debug_logging,
+            set_cache_capacity,
+            clear_cache,
+            split_clauses,
+            char_categories,
+            reading_coverage,
+            pos_transition_matrix,
+            missing_readings,
+            token_ambiguity,
+            chunk_for_rsvp,
+            format_for_print,
+            ra_nuki_forms,
+            load_user_dictionary,
+            prepare_document,
+            get_document_phrases,
+            release_document,
+            analyze_text_command,
+            analyze_text_batch,
+            analyze_and_split
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
-}
\ No newline at end of file
+}
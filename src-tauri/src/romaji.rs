@@ -0,0 +1,305 @@
+// かな（ひらがな）からヘボン式ローマ字への変換
+use serde::{Deserialize, Serialize};
+
+// ローマ字の表記法
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum RomajiStyle {
+    // 伝統的ヘボン式：長音は ō、b・m・p の前の「ん」は m（がっこう → gakkō、しんぶん → shimbun）
+    #[default]
+    Traditional,
+    // 修正ヘボン式：長音は ō、「ん」は常に n（しんぶん → shinbun）
+    Modified,
+    // ワープロ式：かなをそのまま綴る（がっこう → gakkou、しんぶん → shinbun）
+    Wapuro,
+}
+
+// 拗音など 2 文字で 1 音になるかな
+fn digraph(first: char, second: char) -> Option<&'static str> {
+    let romaji = match (first, second) {
+        ('き', 'ゃ') => "kya",
+        ('き', 'ゅ') => "kyu",
+        ('き', 'ょ') => "kyo",
+        ('ぎ', 'ゃ') => "gya",
+        ('ぎ', 'ゅ') => "gyu",
+        ('ぎ', 'ょ') => "gyo",
+        ('し', 'ゃ') => "sha",
+        ('し', 'ゅ') => "shu",
+        ('し', 'ぇ') => "she",
+        ('し', 'ょ') => "sho",
+        ('じ', 'ゃ') => "ja",
+        ('じ', 'ゅ') => "ju",
+        ('じ', 'ぇ') => "je",
+        ('じ', 'ょ') => "jo",
+        ('ち', 'ゃ') => "cha",
+        ('ち', 'ゅ') => "chu",
+        ('ち', 'ぇ') => "che",
+        ('ち', 'ょ') => "cho",
+        ('ぢ', 'ゃ') => "ja",
+        ('ぢ', 'ゅ') => "ju",
+        ('ぢ', 'ょ') => "jo",
+        ('に', 'ゃ') => "nya",
+        ('に', 'ゅ') => "nyu",
+        ('に', 'ょ') => "nyo",
+        ('ひ', 'ゃ') => "hya",
+        ('ひ', 'ゅ') => "hyu",
+        ('ひ', 'ょ') => "hyo",
+        ('び', 'ゃ') => "bya",
+        ('び', 'ゅ') => "byu",
+        ('び', 'ょ') => "byo",
+        ('ぴ', 'ゃ') => "pya",
+        ('ぴ', 'ゅ') => "pyu",
+        ('ぴ', 'ょ') => "pyo",
+        ('み', 'ゃ') => "mya",
+        ('み', 'ゅ') => "myu",
+        ('み', 'ょ') => "myo",
+        ('り', 'ゃ') => "rya",
+        ('り', 'ゅ') => "ryu",
+        ('り', 'ょ') => "ryo",
+        // 外来語の表記（ファ、ティ、ウィ など）
+        ('ふ', 'ぁ') => "fa",
+        ('ふ', 'ぃ') => "fi",
+        ('ふ', 'ぇ') => "fe",
+        ('ふ', 'ぉ') => "fo",
+        ('て', 'ぃ') => "ti",
+        ('で', 'ぃ') => "di",
+        ('と', 'ぅ') => "tu",
+        ('ど', 'ぅ') => "du",
+        ('う', 'ぃ') => "wi",
+        ('う', 'ぇ') => "we",
+        ('う', 'ぉ') => "wo",
+        ('ゔ', 'ぁ') => "va",
+        ('ゔ', 'ぃ') => "vi",
+        ('ゔ', 'ぇ') => "ve",
+        ('ゔ', 'ぉ') => "vo",
+        _ => return None,
+    };
+    Some(romaji)
+}
+
+fn monograph(kana: char) -> Option<&'static str> {
+    let romaji = match kana {
+        'あ' | 'ぁ' => "a",
+        'い' | 'ぃ' => "i",
+        'う' | 'ぅ' => "u",
+        'え' | 'ぇ' => "e",
+        'お' | 'ぉ' => "o",
+        'か' => "ka",
+        'き' => "ki",
+        'く' => "ku",
+        'け' => "ke",
+        'こ' => "ko",
+        'が' => "ga",
+        'ぎ' => "gi",
+        'ぐ' => "gu",
+        'げ' => "ge",
+        'ご' => "go",
+        'さ' => "sa",
+        'し' => "shi",
+        'す' => "su",
+        'せ' => "se",
+        'そ' => "so",
+        'ざ' => "za",
+        'じ' => "ji",
+        'ず' => "zu",
+        'ぜ' => "ze",
+        'ぞ' => "zo",
+        'た' => "ta",
+        'ち' => "chi",
+        'つ' => "tsu",
+        'て' => "te",
+        'と' => "to",
+        'だ' => "da",
+        'ぢ' => "ji",
+        'づ' => "zu",
+        'で' => "de",
+        'ど' => "do",
+        'な' => "na",
+        'に' => "ni",
+        'ぬ' => "nu",
+        'ね' => "ne",
+        'の' => "no",
+        'は' => "ha",
+        'ひ' => "hi",
+        'ふ' => "fu",
+        'へ' => "he",
+        'ほ' => "ho",
+        'ば' => "ba",
+        'び' => "bi",
+        'ぶ' => "bu",
+        'べ' => "be",
+        'ぼ' => "bo",
+        'ぱ' => "pa",
+        'ぴ' => "pi",
+        'ぷ' => "pu",
+        'ぺ' => "pe",
+        'ぽ' => "po",
+        'ま' => "ma",
+        'み' => "mi",
+        'む' => "mu",
+        'め' => "me",
+        'も' => "mo",
+        'や' | 'ゃ' => "ya",
+        'ゆ' | 'ゅ' => "yu",
+        'よ' | 'ょ' => "yo",
+        'ら' => "ra",
+        'り' => "ri",
+        'る' => "ru",
+        'れ' => "re",
+        'ろ' => "ro",
+        'わ' | 'ゎ' => "wa",
+        'ゐ' => "i",
+        'ゑ' => "e",
+        'を' => "o",
+        'ゔ' => "vu",
+        _ => return None,
+    };
+    Some(romaji)
+}
+
+fn macron(vowel: char) -> Option<char> {
+    match vowel {
+        'a' => Some('ā'),
+        'i' => Some('ī'),
+        'u' => Some('ū'),
+        'e' => Some('ē'),
+        'o' => Some('ō'),
+        _ => None,
+    }
+}
+
+fn is_vowel(c: char) -> bool {
+    matches!(c, 'a' | 'i' | 'u' | 'e' | 'o')
+}
+
+// ひらがなをローマ字にする（長音記号「ー」も扱う）。かな以外の文字はそのまま出力する
+pub fn kana_to_romaji(kana: &str, style: RomajiStyle) -> String {
+    let chars: Vec<char> = kana.chars().collect();
+    let mut romaji = String::with_capacity(kana.len());
+    // 直前が促音「っ」かどうか
+    let mut geminate = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if c == 'っ' {
+            geminate = true;
+            i += 1;
+            continue;
+        }
+
+        if c == 'ー' {
+            // 長音記号は直前の母音を伸ばす
+            if let Some(last) = romaji.chars().last().filter(|c| is_vowel(*c)) {
+                match (style, macron(last)) {
+                    (RomajiStyle::Wapuro, _) | (_, None) => romaji.push(last),
+                    (_, Some(long)) => {
+                        romaji.pop();
+                        romaji.push(long);
+                    }
+                }
+            }
+            i += 1;
+            continue;
+        }
+
+        if c == 'ん' {
+            let next = chars
+                .get(i + 1)
+                .and_then(|&next| monograph(next))
+                .and_then(|syllable| syllable.chars().next());
+            match next {
+                // 伝統的ヘボン式では b・m・p の前の「ん」を m にする（しんぶん → shimbun）
+                Some('b' | 'm' | 'p') if style == RomajiStyle::Traditional => romaji.push('m'),
+                // 母音・y の前は区切りを示す（きんよう → kin'yō）
+                Some(next) if is_vowel(next) || next == 'y' => romaji.push_str("n'"),
+                _ => romaji.push('n'),
+            }
+            geminate = false;
+            i += 1;
+            continue;
+        }
+
+        let (syllable, len) = match chars.get(i + 1).and_then(|&next| digraph(c, next)) {
+            Some(syllable) => (syllable, 2),
+            None => match monograph(c) {
+                Some(syllable) => (syllable, 1),
+                None => {
+                    romaji.push(c);
+                    geminate = false;
+                    i += 1;
+                    continue;
+                }
+            },
+        };
+
+        if geminate {
+            // 促音は次の子音を重ねる。ch の前は t にする（まっちゃ → matcha）
+            if syllable.starts_with("ch") {
+                romaji.push('t');
+            } else if let Some(consonant) = syllable.chars().next().filter(|c| !is_vowel(*c)) {
+                romaji.push(consonant);
+            }
+            geminate = false;
+        }
+
+        // 同じ母音の連続と「おう」は長音にする（がっこう → gakkō、おおきい → ōkii）
+        // 「えい」「いい」はそのまま綴る
+        let last = romaji.chars().last();
+        let lengthened = match (style, last, syllable) {
+            (RomajiStyle::Wapuro, _, _) => None,
+            (_, Some('o'), "o" | "u")
+            | (_, Some('u'), "u")
+            | (_, Some('a'), "a")
+            | (_, Some('e'), "e") => last.and_then(macron),
+            _ => None,
+        };
+        match lengthened {
+            Some(long) => {
+                romaji.pop();
+                romaji.push(long);
+            }
+            None => romaji.push_str(syllable),
+        }
+        i += len;
+    }
+
+    romaji
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_long_vowels() {
+        assert_eq!(
+            kana_to_romaji("がっこう", RomajiStyle::Traditional),
+            "gakkō"
+        );
+        assert_eq!(kana_to_romaji("がっこう", RomajiStyle::Wapuro), "gakkou");
+        assert_eq!(kana_to_romaji("こーひー", RomajiStyle::Modified), "kōhī");
+        assert_eq!(
+            kana_to_romaji("せんせい", RomajiStyle::Traditional),
+            "sensei"
+        );
+    }
+
+    #[test]
+    fn test_geminate_and_syllabic_n() {
+        assert_eq!(kana_to_romaji("きっぷ", RomajiStyle::Traditional), "kippu");
+        assert_eq!(
+            kana_to_romaji("まっちゃ", RomajiStyle::Traditional),
+            "matcha"
+        );
+        assert_eq!(
+            kana_to_romaji("しんぶん", RomajiStyle::Traditional),
+            "shimbun"
+        );
+        assert_eq!(kana_to_romaji("しんぶん", RomajiStyle::Modified), "shinbun");
+        assert_eq!(
+            kana_to_romaji("きんよう", RomajiStyle::Traditional),
+            "kin'yō"
+        );
+    }
+}